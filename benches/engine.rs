@@ -0,0 +1,98 @@
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use payment_engine::{Engine, Timestamp, Transaction, TransactionMix, Workload};
+
+const TRANSACTIONS: usize = 100_000;
+
+fn workload(clients: u16, mix: TransactionMix) -> Vec<Transaction> {
+    Workload {
+        clients,
+        transactions: TRANSACTIONS,
+        mix,
+        ..Workload::new(0x5eed)
+    }
+    .generate()
+    .collect()
+}
+
+/// An engine with a fixed clock, so benchmarks don't measure reading the system time.
+fn engine() -> Engine {
+    Engine::default().with_clock(|| Timestamp::UNIX_EPOCH)
+}
+
+fn csv_decode(c: &mut Criterion) {
+    let mut csv = vec![];
+    Workload::new(0x5eed).write_csv(&mut csv).unwrap();
+
+    let mut group = c.benchmark_group("csv");
+    group.throughput(Throughput::Elements(TRANSACTIONS as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(csv.as_slice());
+            reader
+                .deserialize::<Transaction>()
+                .map(|tx| tx.unwrap().id())
+                .fold(0u32, u32::wrapping_add)
+        });
+    });
+    group.finish();
+}
+
+fn process(c: &mut Criterion) {
+    let workloads = [
+        (
+            "deposit_heavy",
+            workload(1_000, TransactionMix::DEPOSIT_HEAVY),
+        ),
+        (
+            "dispute_heavy",
+            workload(1_000, TransactionMix::DISPUTE_HEAVY),
+        ),
+        (
+            "many_clients",
+            workload(u16::MAX, TransactionMix::default()),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("process_transaction");
+    group.throughput(Throughput::Elements(TRANSACTIONS as u64));
+    for (name, txs) in &workloads {
+        group.bench_function(*name, |b| {
+            b.iter_batched(
+                engine,
+                |mut engine| {
+                    for &tx in txs {
+                        let _ = engine.process_transaction(tx);
+                    }
+                    engine
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn accounts(c: &mut Criterion) {
+    let mut engine = engine();
+    for tx in workload(u16::MAX, TransactionMix::DEPOSIT_HEAVY) {
+        let _ = engine.process_transaction(tx);
+    }
+
+    c.bench_function("accounts", |b| {
+        b.iter(|| {
+            engine
+                .accounts()
+                .filter(|(_, account)| !account.total_funds().is_zero())
+                .count()
+        });
+    });
+}
+
+fn engine_default(c: &mut Criterion) {
+    c.bench_function("engine_default", |b| b.iter(Engine::default));
+}
+
+criterion_group!(benches, csv_decode, process, accounts, engine_default);
+criterion_main!(benches);
@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use payment_engine::{Transaction, TransactionKind};
+
+// Raw bytes are read the same way the CLI reads input files.
+fuzz_target!(|data: &[u8]| {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    for tx in reader.deserialize::<Transaction>().filter_map(Result::ok) {
+        if matches!(
+            tx.kind(),
+            TransactionKind::Deposit | TransactionKind::Withdrawal
+        ) {
+            assert!(tx.amount().is_some(), "{tx:?} has no amount");
+        }
+    }
+});
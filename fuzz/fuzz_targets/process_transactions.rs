@@ -0,0 +1,43 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use payment_engine::{Engine, Timestamp, Transaction};
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+    // Rows without a timestamp get a fixed one, for reproducible runs.
+    let mut engine = Engine::default().with_clock(|| Timestamp::UNIX_EPOCH);
+
+    for tx in reader.deserialize::<Transaction>().filter_map(Result::ok) {
+        let before = balances(&engine, tx.client());
+
+        if engine.process_transaction(tx).is_err() && before.is_some() {
+            assert_eq!(
+                balances(&engine, tx.client()),
+                before,
+                "rejected {tx:?} changed the account"
+            );
+        }
+
+        let reconciliation = engine.verify();
+        assert!(
+            reconciliation.is_ok(),
+            "invariants broken after {tx:?}:\n{reconciliation}"
+        );
+    }
+});
+
+fn balances(engine: &Engine, client: u16) -> Option<(String, String, bool)> {
+    engine
+        .accounts()
+        .find(|&(id, _)| id == client)
+        .map(|(_, account)| {
+            (
+                account.available_funds().to_string(),
+                account.held_funds().to_string(),
+                account.is_locked(),
+            )
+        })
+}
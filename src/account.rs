@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::Amount;
 
 /// A client's account in the payment engine.
@@ -9,17 +11,40 @@ pub struct Account {
     held: Amount,
     /// Indicates whether the account is locked due to a chargeback.
     locked: bool,
+    /// The funds removed by chargebacks and not credited back yet.
+    charged_back: Amount,
 }
 
-macro_rules! debug_assert_not_locked {
-    ($self: ident) => {
-        debug_assert!(
-            !$self.is_locked(),
-            "Operation not allowed: account is locked due to a chargeback."
-        );
-    };
+/// Why an [`Account`] operation was refused, leaving the account untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccountError {
+    /// The account is locked due to a chargeback.
+    Locked,
+    /// The account is not locked, but the operation needs a prior chargeback.
+    NotLocked,
+    /// The account does not have enough available funds.
+    InsufficientFunds,
+    /// The account does not have enough held funds.
+    InsufficientHeld,
+    /// The amount is not positive, or a chargeback reversal exceeds the funds charged
+    /// back.
+    InvalidAmount,
+}
+
+impl fmt::Display for AccountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Locked => "account is locked",
+            Self::NotLocked => "account is not locked",
+            Self::InsufficientFunds => "insufficient available funds",
+            Self::InsufficientHeld => "insufficient held funds",
+            Self::InvalidAmount => "invalid amount",
+        })
+    }
 }
 
+impl std::error::Error for AccountError {}
+
 impl Account {
     pub fn total_funds(&self) -> Amount {
         self.available + self.held
@@ -37,54 +62,127 @@ impl Account {
         self.locked
     }
 
-    pub(crate) fn deposit(&mut self, amount: Amount) {
-        debug_assert_not_locked!(self);
+    /// The amount the client owes, when disputes held more than the available funds.
+    pub fn deficit(&self) -> Amount {
+        if self.available < Amount::ZERO {
+            -self.available
+        } else {
+            Amount::ZERO
+        }
+    }
+
+    pub fn deposit(&mut self, amount: Amount) -> Result<(), AccountError> {
+        check_positive(amount)?;
+        self.check_unlocked()?;
 
         self.available += amount;
+        Ok(())
     }
 
     /// Withdrawal a given amount from the account.
     ///
     /// # Errors
     ///
-    /// Returns an error if there are insufficient available funds for the transaction.
-    pub(crate) fn withdraw(&mut self, amount: Amount) -> Result<(), ()> {
-        debug_assert_not_locked!(self);
+    /// Returns an error if the account is locked or there are insufficient available
+    /// funds for the transaction.
+    pub fn withdraw(&mut self, amount: Amount) -> Result<(), AccountError> {
+        check_positive(amount)?;
+        self.check_unlocked()?;
+        self.check_available(amount)?;
 
-        if self.available >= amount {
-            self.available -= amount;
-            Ok(())
-        } else {
-            Err(())
-        }
+        self.available -= amount;
+        Ok(())
     }
 
-    pub(crate) fn hold_funds(&mut self, amount: Amount) -> Result<(), ()> {
-        debug_assert_not_locked!(self);
+    /// Move funds from available to held.
+    pub fn hold_funds(&mut self, amount: Amount) -> Result<(), AccountError> {
+        check_positive(amount)?;
+        self.check_unlocked()?;
+        self.check_available(amount)?;
+        self.hold_funds_allowing_deficit(amount)
+    }
 
-        if self.available >= amount {
-            self.available -= amount;
-            self.held += amount;
-            Ok(())
-        } else {
-            Err(())
-        }
+    /// Hold funds even if there aren't enough available, leaving a deficit.
+    pub fn hold_funds_allowing_deficit(&mut self, amount: Amount) -> Result<(), AccountError> {
+        check_positive(amount)?;
+        self.check_unlocked()?;
+
+        self.available -= amount;
+        self.held += amount;
+        Ok(())
     }
 
-    pub(crate) fn release_funds(&mut self, amount: Amount) {
-        debug_assert_not_locked!(self);
-        debug_assert!(self.held >= amount, "Resolving more than held");
+    /// Move funds from held back to available.
+    pub fn release_funds(&mut self, amount: Amount) -> Result<(), AccountError> {
+        check_positive(amount)?;
+        self.check_unlocked()?;
+        self.check_held(amount)?;
 
         self.held -= amount;
         self.available += amount;
+        Ok(())
     }
 
-    pub(crate) fn chargeback(&mut self, amount: Amount) {
-        debug_assert_not_locked!(self);
-        debug_assert!(self.held >= amount, "Chargeback more than held");
+    /// Remove held funds and lock the account.
+    pub fn chargeback(&mut self, amount: Amount) -> Result<(), AccountError> {
+        check_positive(amount)?;
+        self.check_unlocked()?;
+        self.check_held(amount)?;
 
         self.held -= amount;
+        self.charged_back += amount;
         self.locked = true;
+        Ok(())
+    }
+
+    /// Credit back funds of a chargeback the client successfully contested, up to the
+    /// funds charged back and not credited back yet.
+    ///
+    /// The account stays locked.
+    pub fn reverse_chargeback(&mut self, amount: Amount) -> Result<(), AccountError> {
+        check_positive(amount)?;
+        if !self.locked {
+            return Err(AccountError::NotLocked);
+        }
+        if amount > self.charged_back {
+            return Err(AccountError::InvalidAmount);
+        }
+
+        self.charged_back -= amount;
+        self.available += amount;
+        Ok(())
+    }
+
+    fn check_unlocked(&self) -> Result<(), AccountError> {
+        if self.locked {
+            Err(AccountError::Locked)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_available(&self, amount: Amount) -> Result<(), AccountError> {
+        if self.available >= amount {
+            Ok(())
+        } else {
+            Err(AccountError::InsufficientFunds)
+        }
+    }
+
+    fn check_held(&self, amount: Amount) -> Result<(), AccountError> {
+        if self.held >= amount {
+            Ok(())
+        } else {
+            Err(AccountError::InsufficientHeld)
+        }
+    }
+}
+
+fn check_positive(amount: Amount) -> Result<(), AccountError> {
+    if amount > Amount::ZERO {
+        Ok(())
+    } else {
+        Err(AccountError::InvalidAmount)
     }
 }
 
@@ -95,7 +193,7 @@ mod tests {
     #[test]
     fn test_deposit() {
         let mut account = Account::default();
-        account.deposit(Amount::from(100));
+        account.deposit(Amount::from(100)).unwrap();
         assert_eq!(account.available_funds(), Amount::from(100));
         assert_eq!(account.held_funds(), Amount::from(0));
         assert_eq!(account.total_funds(), Amount::from(100));
@@ -104,7 +202,7 @@ mod tests {
     #[test]
     fn test_withdraw() {
         let mut account = Account::default();
-        account.deposit(Amount::from(100));
+        account.deposit(Amount::from(100)).unwrap();
         assert!(account.withdraw(Amount::from(50)).is_ok());
         assert_eq!(account.available_funds(), Amount::from(50));
         assert_eq!(account.held_funds(), Amount::from(0));
@@ -117,7 +215,7 @@ mod tests {
     #[test]
     fn test_hold_funds() {
         let mut account = Account::default();
-        account.deposit(Amount::from(100));
+        account.deposit(Amount::from(100)).unwrap();
         assert!(account.hold_funds(Amount::from(30)).is_ok());
         assert_eq!(account.available_funds(), Amount::from(70));
         assert_eq!(account.held_funds(), Amount::from(30));
@@ -127,48 +225,148 @@ mod tests {
         assert_eq!(account.available_funds(), Amount::from(70));
     }
 
+    #[test]
+    fn test_hold_funds_allowing_deficit() {
+        let mut account = Account::default();
+        account.deposit(Amount::from(100)).unwrap();
+        account.withdraw(Amount::from(70)).unwrap();
+        account
+            .hold_funds_allowing_deficit(Amount::from(100))
+            .unwrap();
+        assert_eq!(account.available_funds(), Amount::from(-70));
+        assert_eq!(account.held_funds(), Amount::from(100));
+        assert_eq!(account.total_funds(), Amount::from(30));
+        assert_eq!(account.deficit(), Amount::from(70));
+
+        assert!(account.withdraw(Amount::from(1)).is_err());
+
+        account.chargeback(Amount::from(100)).unwrap();
+        assert_eq!(account.total_funds(), Amount::from(-70));
+        assert_eq!(account.deficit(), Amount::from(70));
+    }
+
     #[test]
     fn test_release_funds() {
         let mut account = Account::default();
-        account.deposit(Amount::from(100));
+        account.deposit(Amount::from(100)).unwrap();
         account.hold_funds(Amount::from(40)).unwrap();
-        account.release_funds(Amount::from(20));
+        account.release_funds(Amount::from(20)).unwrap();
         assert_eq!(account.available_funds(), Amount::from(80));
         assert_eq!(account.held_funds(), Amount::from(20));
         assert_eq!(account.total_funds(), Amount::from(100));
     }
 
+    #[test]
+    fn test_release_more_than_held() {
+        let mut account = Account::default();
+        account.deposit(Amount::from(100)).unwrap();
+        account.hold_funds(Amount::from(40)).unwrap();
+
+        assert_eq!(
+            account.release_funds(Amount::from(50)),
+            Err(AccountError::InsufficientHeld)
+        );
+        assert_eq!(
+            account.chargeback(Amount::from(50)),
+            Err(AccountError::InsufficientHeld)
+        );
+        assert_eq!(account.held_funds(), Amount::from(40));
+        assert!(!account.is_locked());
+    }
+
     #[test]
     fn test_chargeback() {
         let mut account = Account::default();
-        account.deposit(Amount::from(100));
+        account.deposit(Amount::from(100)).unwrap();
         account.hold_funds(Amount::from(50)).unwrap();
-        account.chargeback(Amount::from(50));
+        account.chargeback(Amount::from(50)).unwrap();
         assert_eq!(account.available_funds(), Amount::from(50));
         assert_eq!(account.held_funds(), Amount::from(0));
         assert_eq!(account.total_funds(), Amount::from(50));
         assert!(account.is_locked());
 
-        // Further operations should panic in debug mode
-        #[cfg(debug_assertions)]
-        {
-            use std::panic::AssertUnwindSafe;
+        // Further operations are refused, in debug and release builds alike.
+        let locked = Err(AccountError::Locked);
+        assert_eq!(account.deposit(Amount::from(10)), locked);
+        assert_eq!(account.withdraw(Amount::from(10)), locked);
+        assert_eq!(account.hold_funds(Amount::from(10)), locked);
+        assert_eq!(account.release_funds(Amount::from(10)), locked);
+        assert_eq!(account.chargeback(Amount::from(10)), locked);
+        assert_eq!(account.available_funds(), Amount::from(50));
+        assert_eq!(account.total_funds(), Amount::from(50));
 
-            fn test_panic<F: FnOnce() -> R, R>(f: F) -> Result<R, Box<dyn std::any::Any + Send>> {
-                std::panic::catch_unwind(AssertUnwindSafe(f))
-            }
+        assert!(account.reverse_chargeback(Amount::from(50)).is_ok());
+        assert_eq!(account.available_funds(), Amount::from(100));
+        assert!(account.is_locked());
+    }
+
+    #[test]
+    fn test_reverse_chargeback_needs_lock() {
+        let mut account = Account::default();
+        assert_eq!(
+            account.reverse_chargeback(Amount::from(10)),
+            Err(AccountError::NotLocked)
+        );
+        assert_eq!(account.available_funds(), Amount::ZERO);
+    }
 
-            let result = test_panic(|| account.deposit(Amount::from(10)));
-            assert!(result.is_err());
+    #[test]
+    fn test_reverse_chargeback_bounded() {
+        let mut account = Account::default();
+        account.deposit(Amount::from(100)).unwrap();
+        account.hold_funds(Amount::from(30)).unwrap();
+        account.chargeback(Amount::from(30)).unwrap();
 
-            let result = test_panic(|| account.withdraw(Amount::from(10)));
-            assert!(result.is_err());
+        assert_eq!(
+            account.reverse_chargeback(Amount::from(31)),
+            Err(AccountError::InvalidAmount)
+        );
+        account.reverse_chargeback(Amount::from(20)).unwrap();
+        assert_eq!(
+            account.reverse_chargeback(Amount::from(20)),
+            Err(AccountError::InvalidAmount)
+        );
+        account.reverse_chargeback(Amount::from(10)).unwrap();
+        assert_eq!(account.available_funds(), Amount::from(100));
+    }
 
-            let result = test_panic(|| account.hold_funds(Amount::from(10)));
-            assert!(result.is_err());
+    #[test]
+    fn test_non_positive_amounts() {
+        let mut account = Account::default();
+        account.deposit(Amount::from(100)).unwrap();
+        account.hold_funds(Amount::from(50)).unwrap();
 
-            let result = test_panic(|| account.release_funds(Amount::from(10)));
-            assert!(result.is_err());
+        for amount in [Amount::ZERO, Amount::from(-10)] {
+            let invalid = Err(AccountError::InvalidAmount);
+            assert_eq!(account.deposit(amount), invalid);
+            assert_eq!(account.withdraw(amount), invalid);
+            assert_eq!(account.hold_funds(amount), invalid);
+            assert_eq!(account.hold_funds_allowing_deficit(amount), invalid);
+            assert_eq!(account.release_funds(amount), invalid);
+            assert_eq!(account.chargeback(amount), invalid);
         }
+        assert_eq!(account.available_funds(), Amount::from(50));
+        assert_eq!(account.held_funds(), Amount::from(50));
+        assert!(!account.is_locked());
+
+        account.chargeback(Amount::from(50)).unwrap();
+        assert_eq!(
+            account.reverse_chargeback(Amount::from(-10)),
+            Err(AccountError::InvalidAmount)
+        );
+        assert_eq!(account.available_funds(), Amount::from(50));
+    }
+
+    #[test]
+    fn test_hold_funds_locked_before_insufficient() {
+        let mut account = Account::default();
+        account.deposit(Amount::from(10)).unwrap();
+        account.hold_funds(Amount::from(10)).unwrap();
+        account.chargeback(Amount::from(10)).unwrap();
+
+        assert_eq!(
+            account.hold_funds(Amount::from(100)),
+            Err(AccountError::Locked)
+        );
     }
 }
@@ -0,0 +1,181 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufWriter, LineWriter, Read, Write},
+    path::Path,
+};
+
+use flate2::{bufread::MultiGzDecoder, write::GzEncoder};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression format of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The format given by the extension of the path, if any.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Self::Gzip),
+            "zst" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The format given by the magic bytes at the start of the contents, if any.
+    pub fn from_magic(contents: &[u8]) -> Option<Self> {
+        if contents.starts_with(GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if contents.starts_with(ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Wrap the reader to decompress it as it is read, if it is compressed.
+///
+/// The format is given by the extension of the path if it has one, or detected from the
+/// magic bytes.
+pub fn decompress<'a>(
+    path: Option<&Path>,
+    mut reader: impl BufRead + 'a,
+) -> io::Result<Box<dyn Read + 'a>> {
+    let compression = match path.and_then(Compression::from_extension) {
+        Some(compression) => Some(compression),
+        None => Compression::from_magic(reader.fill_buf()?),
+    };
+
+    Ok(match compression {
+        // Concatenated gzip members are read as one stream, as `zcat` does.
+        Some(Compression::Gzip) => Box::new(MultiGzDecoder::new(reader)),
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::with_buffer(reader)?),
+        None => Box::new(reader),
+    })
+}
+
+/// A destination of output, compressing it as it is written when given a path with a
+/// `.gz` or `.zst` extension.
+///
+/// [`Output::finish`] must be called once everything was written, to complete the
+/// compressed stream and report any error writing it.
+pub enum Output {
+    Stdout(io::StdoutLock<'static>),
+    Stderr(LineWriter<io::Stderr>),
+    File(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl Output {
+    /// Create the file, or use `default` without a path.
+    pub fn create(path: Option<&Path>, default: impl FnOnce() -> Self) -> io::Result<Self> {
+        let Some(path) = path else {
+            return Ok(default());
+        };
+
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match Compression::from_extension(path) {
+            Some(Compression::Gzip) => {
+                Self::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Some(Compression::Zstd) => Self::Zstd(zstd::Encoder::new(file, 0)?),
+            None => Self::File(file),
+        })
+    }
+
+    pub fn stdout() -> Self {
+        Self::Stdout(io::stdout().lock())
+    }
+
+    /// Stderr, buffered so that each line is written at once while still showing up as
+    /// soon as it is complete.
+    pub fn stderr() -> Self {
+        Self::Stderr(LineWriter::new(io::stderr()))
+    }
+
+    /// Complete the output, flushing it.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Stdout(mut stdout) => stdout.flush(),
+            Self::Stderr(mut stderr) => stderr.flush(),
+            Self::File(mut file) => file.flush(),
+            Self::Gzip(encoder) => encoder.finish()?.flush(),
+            Self::Zstd(encoder) => encoder.finish()?.flush(),
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Self::Stdout(stdout) => stdout,
+            Self::Stderr(stderr) => stderr,
+            Self::File(file) => file,
+            Self::Gzip(encoder) => encoder,
+            Self::Zstd(encoder) => encoder,
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(extension: &str) {
+        let dir = std::env::temp_dir().join(format!("payment-engine-compress-{extension}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("output.csv.{extension}"));
+
+        let contents = "type,client,tx,amount\ndeposit,1,1,10\n".repeat(1000);
+        let mut output = Output::create(Some(&path), Output::stdout).unwrap();
+        output.write_all(contents.as_bytes()).unwrap();
+        output.finish().unwrap();
+
+        let compressed = std::fs::read(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(compressed.len() < contents.len() / 10);
+
+        // Detected by the magic bytes alone.
+        let mut decompressed = String::new();
+        decompress(None, compressed.as_slice())
+            .unwrap()
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, contents);
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        round_trip("gz");
+    }
+
+    #[test]
+    fn test_zstd_round_trip() {
+        round_trip("zst");
+    }
+
+    #[test]
+    fn test_uncompressed() {
+        let contents = b"type,client,tx,amount\n";
+        let mut read = vec![];
+        decompress(Some(Path::new("input.csv")), &contents[..])
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, contents);
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use payment_engine::{
+    ConfigError, DisputePolicy, DisputeTransition, Engine, ExpiryAction, LimitsConfig, Schema,
+};
+
+use crate::{CliError, EngineArgs, SchemaArgs, StatsFormat};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Defaults for the engine options, loaded from the TOML file passed with `--config`.
+///
+/// Options given on the command line take precedence.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Limits file, relative to the config file.
+    limits: Option<PathBuf>,
+    /// Schema file of the inputs, relative to the config file.
+    schema: Option<PathBuf>,
+    dispute_window_days: Option<u64>,
+    dispute_deadline_days: Option<u64>,
+    chargeback_expired_disputes: bool,
+    allow_representment: bool,
+    allow_deficit: bool,
+    idempotent: bool,
+    strict_ordering: bool,
+    fail_on_anomalies: bool,
+    verify: bool,
+    stats: Option<StatsFormat>,
+}
+
+impl Config {
+    pub fn from_path(path: &Path) -> Result<Self, CliError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| CliError::Io(format!("{}: {err}", path.display())))?;
+        let mut config: Self = toml::from_str(&contents)
+            .map_err(|err| CliError::Parse(format!("{}: {err}", path.display())))?;
+
+        if let Some(dir) = path.parent() {
+            for path in [&mut config.limits, &mut config.schema]
+                .into_iter()
+                .flatten()
+            {
+                *path = dir.join(&*path);
+            }
+        }
+        Ok(config)
+    }
+
+    /// The schema of the inputs, from the command line or the config file.
+    pub fn schema(&self, args: &SchemaArgs) -> Result<Schema, CliError> {
+        let mut schema = match args.schema.as_ref().or(self.schema.as_ref()) {
+            Some(path) => Schema::from_path(path).map_err(|err| config_error(path, err))?,
+            None => Schema::default(),
+        };
+        if let Some(delimiter) = args.delimiter {
+            let delimiter = u8::try_from(delimiter)
+                .ok()
+                .filter(u8::is_ascii)
+                .ok_or_else(|| CliError::Usage(format!("delimiter {delimiter:?} is not ASCII")))?;
+            schema = schema.with_delimiter(delimiter);
+        }
+        if args.no_headers {
+            schema = schema.without_headers();
+        }
+        Ok(schema)
+    }
+}
+
+/// The engine options of a run, from the command line and the config file.
+pub struct Settings {
+    limits: Option<PathBuf>,
+    pub dispute_policy: DisputePolicy,
+    idempotent: bool,
+    strict_ordering: bool,
+    pub fail_on_anomalies: bool,
+    pub verify: bool,
+    pub stats: Option<StatsFormat>,
+}
+
+impl Settings {
+    pub fn new(args: &EngineArgs, config: &Config) -> Result<Self, CliError> {
+        let days = |days: u64| {
+            days.checked_mul(SECS_PER_DAY)
+                .map(Duration::from_secs)
+                .ok_or_else(|| CliError::Usage(format!("{days} days is too long a duration")))
+        };
+
+        let mut dispute_policy = DisputePolicy {
+            window: args
+                .dispute_window_days
+                .or(config.dispute_window_days)
+                .map(days)
+                .transpose()?,
+            deadline: args
+                .dispute_deadline_days
+                .or(config.dispute_deadline_days)
+                .map(days)
+                .transpose()?,
+            allow_deficit: args.allow_deficit || config.allow_deficit,
+            ..DisputePolicy::default()
+        };
+        if args.chargeback_expired_disputes || config.chargeback_expired_disputes {
+            dispute_policy.on_expiry = ExpiryAction::Chargeback;
+        }
+        if args.allow_representment || config.allow_representment {
+            dispute_policy.transitions = dispute_policy
+                .transitions
+                .with(DisputeTransition::Represent);
+        }
+
+        Ok(Self {
+            limits: args.limits.clone().or_else(|| config.limits.clone()),
+            dispute_policy,
+            idempotent: args.idempotent || config.idempotent,
+            strict_ordering: args.strict_ordering || config.strict_ordering,
+            fail_on_anomalies: args.fail_on_anomalies || config.fail_on_anomalies,
+            verify: args.verify || config.verify,
+            stats: args.stats.or(config.stats),
+        })
+    }
+
+    pub fn engine(&self) -> Result<Engine, CliError> {
+        let mut engine = Engine::default().with_dispute_policy(self.dispute_policy.clone());
+        if self.idempotent {
+            engine = engine.with_idempotency();
+        }
+        if self.strict_ordering {
+            engine = engine.with_strict_ordering();
+        }
+        if let Some(path) = &self.limits {
+            let limits = LimitsConfig::from_path(path).map_err(|err| config_error(path, err))?;
+            engine = engine.with_limits(limits);
+        }
+        Ok(engine)
+    }
+}
+
+fn config_error(path: &Path, err: ConfigError) -> CliError {
+    match err {
+        ConfigError::Io(_) => CliError::Io(format!("{}: {err}", path.display())),
+        ConfigError::Parse(_) => CliError::Parse(format!("{}: {err}", path.display())),
+    }
+}
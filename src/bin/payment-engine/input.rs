@@ -0,0 +1,155 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader},
+    path::PathBuf,
+};
+
+use payment_engine::{Schema, Transaction};
+
+use crate::{CliError, compress};
+
+/// A source of transactions.
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+    /// The body of a request to `serve`, numbered in order of arrival.
+    Batch(u64, Vec<u8>),
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdin => f.write_str("<stdin>"),
+            Self::File(path) => path.display().fmt(f),
+            Self::Batch(id, _) => write!(f, "<batch {id}>"),
+        }
+    }
+}
+
+impl Input {
+    /// Expand the input arguments: `-` reads stdin, and patterns with glob wildcards
+    /// are replaced by the matching files, in alphabetical order.
+    pub fn expand(args: &[String]) -> Result<Vec<Self>, CliError> {
+        let mut inputs = vec![];
+        for arg in args {
+            if arg == "-" {
+                inputs.push(Self::Stdin);
+            } else if arg.contains(['*', '?', '[']) {
+                let matched = inputs.len();
+                let paths = glob::glob(arg)
+                    .map_err(|err| CliError::Usage(format!("invalid pattern {arg:?}: {err}")))?;
+                for path in paths {
+                    inputs.push(Self::File(
+                        path.map_err(|err| CliError::Io(err.to_string()))?,
+                    ));
+                }
+                if inputs.len() == matched {
+                    return Err(CliError::Io(format!("no files match {arg:?}")));
+                }
+            } else {
+                inputs.push(Self::File(arg.into()));
+            }
+        }
+
+        Ok(inputs)
+    }
+
+    /// Open the input, decompressing it if it is compressed with gzip or zstd.
+    fn open(&self) -> Result<Box<dyn io::Read + '_>, CliError> {
+        let opened = match self {
+            Self::Stdin => compress::decompress(None, io::stdin().lock()),
+            Self::File(path) => File::open(path)
+                .and_then(|file| compress::decompress(Some(path), BufReader::new(file))),
+            Self::Batch(_, body) => compress::decompress(None, body.as_slice()),
+        };
+        opened.map_err(|err| CliError::Io(format!("{self}: {err}")))
+    }
+}
+
+/// Where a transaction was read from.
+#[derive(Clone, Copy)]
+pub struct Location<'a> {
+    pub input: &'a Input,
+    pub line: u64,
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.input, self.line)
+    }
+}
+
+/// Read every transaction of the inputs, in order.
+///
+/// Each input has its own header row, unless the schema has none. A malformed row stops
+/// reading with an error pointing at it.
+pub fn read_transactions(
+    inputs: &[Input],
+    schema: &Schema,
+    mut f: impl FnMut(Location<'_>, Transaction) -> Result<(), CliError>,
+) -> Result<(), CliError> {
+    read_rows(inputs, schema, |location, tx| f(location, tx?))
+}
+
+/// Read every row of the inputs as a transaction, in order, including malformed ones.
+///
+/// Errors reading the inputs themselves still stop reading.
+pub fn read_rows(
+    inputs: &[Input],
+    schema: &Schema,
+    mut f: impl FnMut(Location<'_>, Result<Transaction, CliError>) -> Result<(), CliError>,
+) -> Result<(), CliError> {
+    for input in inputs {
+        let mut reader = schema
+            .reader_builder()
+            .trim(csv::Trim::All)
+            .from_reader(input.open()?);
+        let headers = if reader.has_headers() {
+            Some(
+                reader
+                    .headers()
+                    .map_err(|err| csv_error(input, err))?
+                    .clone(),
+            )
+        } else {
+            None
+        };
+        let mapping = schema
+            .mapping(headers.as_ref())
+            .map_err(|err| CliError::Parse(format!("{input}: {err}")))?;
+
+        let mut record = csv::StringRecord::new();
+        loop {
+            match reader.read_record(&mut record) {
+                Ok(true) => {}
+                Ok(false) => break,
+                // Rows with the wrong number of fields can be skipped.
+                Err(err) if matches!(err.kind(), csv::ErrorKind::UnequalLengths { .. }) => {
+                    let line = err.position().map_or(0, |position| position.line());
+                    let location = Location { input, line };
+                    f(location, Err(CliError::Parse(format!("{location}: {err}"))))?;
+                    continue;
+                }
+                Err(err) => return Err(csv_error(input, err)),
+            }
+
+            let line = record.position().map_or(0, |position| position.line());
+            let location = Location { input, line };
+            let tx = mapping
+                .transaction(&record)
+                .map_err(|err| CliError::Parse(format!("{location}: {err}")));
+            f(location, tx)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn csv_error(input: &Input, err: csv::Error) -> CliError {
+    if err.is_io_error() {
+        CliError::Io(format!("{input}: {err}"))
+    } else {
+        CliError::Parse(format!("{input}: {err}"))
+    }
+}
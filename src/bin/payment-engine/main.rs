@@ -0,0 +1,715 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use payment_engine::{
+    AccountFilter, AccountReport, Column, Engine, Rejection, Schema, Severity, SortKey, Timestamp,
+    Transaction, Workload,
+};
+
+use self::{
+    compress::Output,
+    config::{Config, Settings},
+    input::{Input, Location},
+    rejections::Rejections,
+    serve::Service,
+    validate::Validation,
+};
+
+mod compress;
+mod config;
+mod input;
+mod output;
+mod rejections;
+mod serve;
+mod validate;
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  Success, every transaction was accepted
+  1  Processed, but some transactions were rejected
+  2  Invalid arguments
+  3  Malformed input or configuration
+  4  I/O error
+  5  Invariant violations found by --verify
+  6  High-severity anomalies found with --fail-on-anomalies";
+
+/// Process payment transactions read from CSV files.
+///
+/// Without a subcommand, transactions are processed and the resulting accounts written
+/// to stdout, as with `process`.
+#[derive(Parser)]
+#[command(
+    version,
+    after_help = EXIT_CODES,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    /// TOML file with defaults for the engine options.
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Write the output to this file instead of stdout, compressed if it ends with `.gz`
+    /// or `.zst`.
+    #[arg(long, global = true, value_name = "FILE")]
+    output: Option<PathBuf>,
+    /// Report rejected transactions to this file instead of stderr, compressed if it ends
+    /// with `.gz` or `.zst`.
+    #[arg(long, global = true, value_name = "FILE")]
+    rejections: Option<PathBuf>,
+    /// Summarise the rejected transactions by reason once processed, instead of
+    /// reporting each of them.
+    #[arg(long, global = true)]
+    summarize_rejections: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    process: ProcessArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Process transactions and write the resulting accounts.
+    Process(ProcessArgs),
+    /// Process transactions and write a client's statement.
+    Statement(StatementArgs),
+    /// Process transactions and write the trial balance of the ledger.
+    TrialBalance(RunArgs),
+    /// Process transactions in a scratch engine and report the outcome, without
+    /// writing balances.
+    Validate(RunArgs),
+    /// Process transactions and write the engine's statistics, as text or in the
+    /// `--stats` format.
+    Stats(RunArgs),
+    /// Rewrite transactions in the canonical CSV format, without processing them.
+    Convert(InputArgs),
+    /// Process transactions and write them timestamped as processed, for `replay`.
+    Snapshot(RunArgs),
+    /// Rebuild the engine from a snapshot, then process transactions on top of it and
+    /// write the resulting accounts.
+    Replay(ReplayArgs),
+    /// Write seeded synthetic transactions.
+    Generate(GenerateArgs),
+    /// Keep running to process batches of transactions posted over HTTP, exporting
+    /// metrics in the Prometheus format at `/metrics`.
+    Serve(ServeArgs),
+}
+
+#[derive(Args)]
+struct InputArgs {
+    /// CSV files processed in order, glob patterns, or `-` for stdin.
+    #[arg(value_name = "INPUT", required = true)]
+    inputs: Vec<String>,
+    #[command(flatten)]
+    schema: SchemaArgs,
+}
+
+/// Layout of the input files, when they don't use the canonical format.
+#[derive(Args)]
+struct SchemaArgs {
+    /// TOML file with the columns, delimiter and type aliases of the inputs.
+    #[arg(long, value_name = "FILE")]
+    schema: Option<PathBuf>,
+    /// Field delimiter of the inputs.
+    #[arg(long, value_name = "CHAR")]
+    delimiter: Option<char>,
+    /// The inputs have no header row, their columns are found by position.
+    #[arg(long)]
+    no_headers: bool,
+}
+
+/// Options of the engine processing the transactions.
+#[derive(Args)]
+struct EngineArgs {
+    /// TOML file with per-client limits.
+    #[arg(long, value_name = "FILE")]
+    limits: Option<PathBuf>,
+    /// Reject disputes of deposits older than this.
+    #[arg(long, value_name = "DAYS")]
+    dispute_window_days: Option<u64>,
+    /// Close disputes open for longer than this.
+    #[arg(long, value_name = "DAYS")]
+    dispute_deadline_days: Option<u64>,
+    /// Charge back disputes past their deadline instead of resolving them.
+    #[arg(long)]
+    chargeback_expired_disputes: bool,
+    /// Allow disputing charged back deposits again.
+    #[arg(long)]
+    allow_representment: bool,
+    /// Hold disputed funds even if already withdrawn, leaving a deficit.
+    #[arg(long)]
+    allow_deficit: bool,
+    /// Ignore exact copies of already processed rows, as resent when a batch is retried,
+    /// and reject rows reusing a deposit or withdrawal ID with other contents.
+    #[arg(long)]
+    idempotent: bool,
+    /// Reject rows older than a previous row of the same client, instead of applying
+    /// them and reporting them as out of order.
+    #[arg(long)]
+    strict_ordering: bool,
+    /// Fail if any transaction is rejected as a high-severity anomaly, such as a
+    /// transaction ID reused for a different amount.
+    #[arg(long)]
+    fail_on_anomalies: bool,
+    /// Reconcile the engine after processing, writing the report to stderr and failing
+    /// on discrepancies.
+    #[arg(long)]
+    verify: bool,
+    /// Write statistics about the run to stderr once processed, as text or in the
+    /// Prometheus text format.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text"
+    )]
+    stats: Option<StatsFormat>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum StatsFormat {
+    Text,
+    Prometheus,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+#[derive(Args)]
+struct ReportArgs {
+    /// Order of the accounts: client, total or available.
+    #[arg(long, value_name = "KEY", default_value = "client")]
+    sort: SortKey,
+    /// Only write accounts that are locked, unlocked, nonzero or has-held.
+    #[arg(long, value_name = "FILTER", value_delimiter = ',')]
+    filter: Vec<AccountFilter>,
+    /// Columns to write, out of client, available, held, total, locked, deficit and
+    /// transactions.
+    #[arg(long, value_name = "COLUMNS", value_delimiter = ',')]
+    columns: Vec<Column>,
+    /// Write amounts with this many decimal places, up to 28.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u8).range(..=AccountReport::MAX_DECIMALS as i64)
+    )]
+    decimals: Option<u8>,
+    /// Add the number of transactions processed for each client.
+    #[arg(long)]
+    transaction_counts: bool,
+}
+
+#[derive(Args)]
+struct ProcessArgs {
+    #[command(flatten)]
+    run: RunArgs,
+    #[command(flatten)]
+    report: ReportArgs,
+}
+
+#[derive(Args)]
+struct StatementArgs {
+    #[command(flatten)]
+    run: RunArgs,
+    #[arg(long)]
+    client: u16,
+    /// Start of the period, inclusive, as RFC 3339 or epoch milliseconds.
+    #[arg(long)]
+    from: Option<Timestamp>,
+    /// End of the period, exclusive.
+    #[arg(long)]
+    to: Option<Timestamp>,
+    #[arg(long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    /// Snapshot written by `snapshot`, or `-` for stdin.
+    snapshot: String,
+    /// CSV files processed after the snapshot, glob patterns, or `-` for stdin.
+    #[arg(value_name = "INPUT")]
+    inputs: Vec<String>,
+    #[command(flatten)]
+    schema: SchemaArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    report: ReportArgs,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Address to listen on, with port 0 for any free port.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:9898")]
+    listen: String,
+    /// Largest batch accepted, in bytes, bigger ones are refused with 413.
+    #[arg(long, value_name = "BYTES", default_value_t = 16 << 20)]
+    max_body_bytes: u64,
+    #[command(flatten)]
+    schema: SchemaArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+#[derive(Args)]
+struct GenerateArgs {
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Number of clients [default: 1000]
+    #[arg(long)]
+    clients: Option<u16>,
+    /// Number of transactions [default: 100000]
+    #[arg(long)]
+    transactions: Option<usize>,
+    /// Relative weight of deposits [default: 40]
+    #[arg(long, value_name = "WEIGHT")]
+    deposit: Option<u32>,
+    /// Relative weight of withdrawals [default: 35]
+    #[arg(long, value_name = "WEIGHT")]
+    withdrawal: Option<u32>,
+    /// Relative weight of disputes [default: 15]
+    #[arg(long, value_name = "WEIGHT")]
+    dispute: Option<u32>,
+    /// Relative weight of resolves [default: 6]
+    #[arg(long, value_name = "WEIGHT")]
+    resolve: Option<u32>,
+    /// Relative weight of chargebacks [default: 4]
+    #[arg(long, value_name = "WEIGHT")]
+    chargeback: Option<u32>,
+    /// Relative weight of transactions the engine always rejects [default: 0]
+    #[arg(long, value_name = "WEIGHT")]
+    invalid: Option<u32>,
+}
+
+/// An error stopping the CLI, each kind with its own exit code.
+#[derive(Debug)]
+pub enum CliError {
+    /// Invalid arguments, not caught while parsing them.
+    Usage(String),
+    /// Malformed input or configuration.
+    Parse(String),
+    Io(String),
+    /// Reconciliation found discrepancies.
+    Invariants,
+    /// Transactions were rejected as high-severity anomalies, and the run set to fail.
+    Anomalies(usize),
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Self::Usage(_) => 2,
+            Self::Parse(_) => 3,
+            Self::Io(_) => 4,
+            Self::Invariants => 5,
+            Self::Anomalies(_) => 6,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(msg) | Self::Parse(msg) | Self::Io(msg) => f.write_str(msg),
+            Self::Invariants => f.write_str("reconciliation found discrepancies"),
+            Self::Anomalies(count) => write!(f, "transactions rejected as anomalies: {count}"),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err.to_string())
+    }
+}
+
+impl From<csv::Error> for CliError {
+    fn from(err: csv::Error) -> Self {
+        if err.is_io_error() {
+            Self::Io(err.to_string())
+        } else {
+            Self::Parse(err.to_string())
+        }
+    }
+}
+
+/// How a run that didn't fail went.
+enum Outcome {
+    Clean,
+    Rejections,
+}
+
+impl Outcome {
+    fn new(rejected: usize) -> Self {
+        if rejected == 0 {
+            Self::Clean
+        } else {
+            Self::Rejections
+        }
+    }
+}
+
+/// The transactions of a run that weren't applied.
+#[derive(Default)]
+struct Tally {
+    /// Rejected transactions, not counting those ignored as replays.
+    rejected: usize,
+    /// Rejected transactions of high severity.
+    anomalies: usize,
+}
+
+impl Tally {
+    fn record(&mut self, result: Result<(), Rejection>) {
+        self.rejected += is_rejected(result) as usize;
+        self.anomalies +=
+            result.is_err_and(|rejection| rejection.severity() == Severity::High) as usize;
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(Outcome::Clean) => ExitCode::SUCCESS,
+        Ok(Outcome::Rejections) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<Outcome, CliError> {
+    let config = match &cli.config {
+        Some(path) => Config::from_path(path)?,
+        None => Config::default(),
+    };
+
+    let create = |path: Option<&Path>, default: fn() -> Output| {
+        Output::create(path, default).map_err(|err| {
+            let path = path.map_or_else(String::new, |path| path.display().to_string());
+            CliError::Io(format!("{path}: {err}"))
+        })
+    };
+    let mut out = create(cli.output.as_deref(), Output::stdout)?;
+    let mut rejections = Rejections::new(
+        create(cli.rejections.as_deref(), Output::stderr)?,
+        cli.summarize_rejections,
+    );
+
+    let command = cli.command.unwrap_or(Command::Process(cli.process));
+    let outcome = run_command(command, &config, &mut out, &mut rejections);
+    rejections.finish()?;
+    out.finish()?;
+    outcome
+}
+
+fn run_command(
+    command: Command,
+    config: &Config,
+    out: &mut Output,
+    rejections: &mut Rejections,
+) -> Result<Outcome, CliError> {
+    match command {
+        Command::Process(args) => {
+            let (engine, settings, tally) = process(&args.run, config, rejections)?;
+            write_stats(&engine, &settings)?;
+            write_accounts(&mut *out, &engine, &settings, &args.report)?;
+            Ok(Outcome::new(tally.rejected))
+        }
+        Command::Statement(args) => {
+            let (engine, settings, tally) = process(&args.run, config, rejections)?;
+            write_stats(&engine, &settings)?;
+            let statement = engine.statement(
+                args.client,
+                args.from.unwrap_or(Timestamp::from_millis(i64::MIN)),
+                args.to.unwrap_or(Timestamp::from_millis(i64::MAX)),
+            );
+            match args.format {
+                Format::Csv => output::write_statement_csv(&mut *out, &statement)?,
+                Format::Json => output::write_statement_json(&mut *out, &statement)?,
+            }
+            Ok(Outcome::new(tally.rejected))
+        }
+        Command::TrialBalance(args) => {
+            let (engine, settings, tally) = process(&args, config, rejections)?;
+            write_stats(&engine, &settings)?;
+            output::write_trial_balance(&mut *out, &engine.ledger().trial_balance())?;
+            Ok(Outcome::new(tally.rejected))
+        }
+        Command::Validate(args) => {
+            let settings = Settings::new(&args.engine, config)?;
+            let mut engine = settings.engine()?;
+            let inputs = Input::expand(&args.input.inputs)?;
+            let schema = config.schema(&args.input.schema)?;
+
+            // Malformed rows are reported and counted rather than stopping the run, so
+            // that the whole input is validated.
+            let mut validation = Validation::default();
+            input::read_rows(&inputs, &schema, |location, tx| {
+                match tx {
+                    Ok(tx) => {
+                        let result = process_transaction(&mut engine, location, tx, rejections)?;
+                        validation.record(&tx, result);
+                    }
+                    Err(err) => {
+                        rejections.malformed(err)?;
+                        validation.record_malformed();
+                    }
+                }
+                Ok(())
+            })?;
+            verify(&engine, &settings)?;
+            write_stats(&engine, &settings)?;
+
+            validation.write_json(&mut *out, &engine)?;
+            if validation.malformed() > 0 {
+                return Err(CliError::Parse(format!(
+                    "{} malformed rows",
+                    validation.malformed()
+                )));
+            }
+            check_anomalies(&settings, validation.anomalies())?;
+            Ok(Outcome::new(validation.rejected()))
+        }
+        Command::Stats(args) => {
+            let (engine, settings, tally) = process(&args, config, rejections)?;
+            let stats = engine.stats();
+            match settings.stats.unwrap_or(StatsFormat::Text) {
+                StatsFormat::Text => write!(out, "{stats}")?,
+                StatsFormat::Prometheus => stats.write_prometheus(&mut *out)?,
+            }
+            Ok(Outcome::new(tally.rejected))
+        }
+        Command::Convert(args) => {
+            let inputs = Input::expand(&args.inputs)?;
+            let schema = config.schema(&args.schema)?;
+            let mut wtr = csv::Writer::from_writer(&mut *out);
+            input::read_transactions(&inputs, &schema, |_, tx| Ok(wtr.serialize(tx)?))?;
+            wtr.flush()?;
+            Ok(Outcome::Clean)
+        }
+        Command::Snapshot(args) => {
+            let settings = Settings::new(&args.engine, config)?;
+            let mut engine = settings.engine()?;
+            let inputs = Input::expand(&args.input.inputs)?;
+            let schema = config.schema(&args.input.schema)?;
+
+            // Timestamp every transaction before processing, so that replaying the
+            // snapshot with the same options rebuilds the same state.
+            let mut wtr = csv::Writer::from_writer(&mut *out);
+            let mut tally = Tally::default();
+            input::read_transactions(&inputs, &schema, |location, tx| {
+                let tx = tx.with_timestamp(tx.timestamp().unwrap_or_else(Timestamp::now));
+                tally.record(process_transaction(&mut engine, location, tx, rejections)?);
+                Ok(wtr.serialize(tx)?)
+            })?;
+            wtr.flush()?;
+
+            verify(&engine, &settings)?;
+            write_stats(&engine, &settings)?;
+            check_anomalies(&settings, tally.anomalies)?;
+            Ok(Outcome::new(tally.rejected))
+        }
+        Command::Replay(args) => {
+            let settings = Settings::new(&args.engine, config)?;
+            let mut engine = settings.engine()?;
+
+            // Rejections were reported when the snapshot was taken.
+            let snapshot = Input::expand(std::slice::from_ref(&args.snapshot))?;
+            input::read_transactions(&snapshot, &Schema::default(), |_, tx| {
+                let _ = engine.process_transaction(tx);
+                Ok(())
+            })?;
+
+            let inputs = Input::expand(&args.inputs)?;
+            let schema = config.schema(&args.schema)?;
+            let tally = process_inputs(&mut engine, &inputs, &schema, rejections)?;
+            verify(&engine, &settings)?;
+            write_stats(&engine, &settings)?;
+            check_anomalies(&settings, tally.anomalies)?;
+            write_accounts(&mut *out, &engine, &settings, &args.report)?;
+            Ok(Outcome::new(tally.rejected))
+        }
+        Command::Generate(args) => {
+            generate(out, args)?;
+            Ok(Outcome::Clean)
+        }
+        Command::Serve(args) => {
+            let settings = Settings::new(&args.engine, config)?;
+            let schema = config.schema(&args.schema)?;
+            Service::new(settings, schema, args.max_body_bytes, rejections)?.run(&args.listen)?;
+            Ok(Outcome::Clean)
+        }
+    }
+}
+
+/// Process the inputs into a new engine, verifying it and checking for anomalies if
+/// enabled.
+///
+/// Returns the engine, its settings and the tally of rejected transactions.
+fn process(
+    args: &RunArgs,
+    config: &Config,
+    rejections: &mut Rejections,
+) -> Result<(Engine, Settings, Tally), CliError> {
+    let settings = Settings::new(&args.engine, config)?;
+    let mut engine = settings.engine()?;
+    let inputs = Input::expand(&args.input.inputs)?;
+    let schema = config.schema(&args.input.schema)?;
+    let tally = process_inputs(&mut engine, &inputs, &schema, rejections)?;
+    verify(&engine, &settings)?;
+    check_anomalies(&settings, tally.anomalies)?;
+    Ok((engine, settings, tally))
+}
+
+/// Process every transaction of the inputs.
+///
+/// Returns the tally of rejected transactions.
+fn process_inputs(
+    engine: &mut Engine,
+    inputs: &[Input],
+    schema: &Schema,
+    rejections: &mut Rejections,
+) -> Result<Tally, CliError> {
+    let mut tally = Tally::default();
+    input::read_transactions(inputs, schema, |location, tx| {
+        tally.record(process_transaction(engine, location, tx, rejections)?);
+        Ok(())
+    })?;
+    Ok(tally)
+}
+
+/// Process a transaction, reporting it to `rejections` if rejected, ignored or applied
+/// out of order.
+fn process_transaction(
+    engine: &mut Engine,
+    location: Location<'_>,
+    tx: Transaction,
+    rejections: &mut Rejections,
+) -> io::Result<Result<(), Rejection>> {
+    let out_of_order = engine.is_out_of_order(&tx);
+    let result = engine.process_transaction(tx);
+    match result {
+        Ok(()) if out_of_order => rejections.out_of_order(location, &tx)?,
+        Ok(()) => {}
+        Err(rejection) => rejections.rejected(location, &tx, rejection)?,
+    }
+    Ok(result)
+}
+
+/// Whether the transaction was rejected, rather than accepted or ignored as a replay.
+fn is_rejected(result: Result<(), Rejection>) -> bool {
+    result.is_err_and(|rejection| rejection != Rejection::Replayed)
+}
+
+fn check_anomalies(settings: &Settings, anomalies: usize) -> Result<(), CliError> {
+    if settings.fail_on_anomalies && anomalies > 0 {
+        Err(CliError::Anomalies(anomalies))
+    } else {
+        Ok(())
+    }
+}
+
+fn verify(engine: &Engine, settings: &Settings) -> Result<(), CliError> {
+    if !settings.verify {
+        return Ok(());
+    }
+
+    let reconciliation = engine.verify();
+    eprint!("{reconciliation}");
+    if reconciliation.is_ok() {
+        Ok(())
+    } else {
+        Err(CliError::Invariants)
+    }
+}
+
+fn write_stats(engine: &Engine, settings: &Settings) -> Result<(), CliError> {
+    let Some(format) = settings.stats else {
+        return Ok(());
+    };
+
+    let stats = engine.stats();
+    match format {
+        StatsFormat::Text => eprint!("{stats}"),
+        StatsFormat::Prometheus => stats.write_prometheus(io::stderr().lock())?,
+    }
+    Ok(())
+}
+
+fn write_accounts(
+    out: impl Write,
+    engine: &Engine,
+    settings: &Settings,
+    args: &ReportArgs,
+) -> Result<(), CliError> {
+    let mut columns = args.columns.clone();
+    if columns.is_empty() {
+        columns = Column::DEFAULT.to_vec();
+        // Only show the deficit when it can be non-zero, to keep the default output format.
+        if settings.dispute_policy.allow_deficit {
+            columns.push(Column::Deficit);
+        }
+    }
+    if args.transaction_counts && !columns.contains(&Column::Transactions) {
+        columns.push(Column::Transactions);
+    }
+
+    let mut report = AccountReport::new(engine)
+        .sort_by(args.sort)
+        .columns(columns);
+    for &filter in &args.filter {
+        report = report.filter(filter);
+    }
+    if let Some(decimals) = args.decimals {
+        report = report.decimals(decimals);
+    }
+
+    Ok(report.write_csv(out)?)
+}
+
+/// Write a generated workload of synthetic transactions.
+fn generate(out: impl Write, args: GenerateArgs) -> Result<(), CliError> {
+    let mut workload = Workload::new(args.seed);
+    let mix = &mut workload.mix;
+    for (weight, arg) in [
+        (&mut mix.deposit, args.deposit),
+        (&mut mix.withdrawal, args.withdrawal),
+        (&mut mix.dispute, args.dispute),
+        (&mut mix.resolve, args.resolve),
+        (&mut mix.chargeback, args.chargeback),
+        (&mut mix.invalid, args.invalid),
+    ] {
+        if let Some(arg) = arg {
+            *weight = arg;
+        }
+    }
+    if let Some(clients) = args.clients {
+        workload.clients = clients;
+    }
+    if let Some(transactions) = args.transactions {
+        workload.transactions = transactions;
+    }
+
+    Ok(workload.write_csv(out)?)
+}
@@ -0,0 +1,137 @@
+use std::io::{self, Write};
+
+use payment_engine::{Balances, Statement, TrialBalance};
+
+use crate::CliError;
+
+/// Write the statement as a single table.
+///
+/// The postings are preceded by the opening balances and followed by the closing ones,
+/// then by the number of disputes and one row per dispute summary total, all in the
+/// `amount` column.
+pub fn write_statement_csv(out: impl Write, statement: &Statement) -> Result<(), CliError> {
+    let mut wtr = csv::Writer::from_writer(out);
+    wtr.write_record([
+        "type",
+        "timestamp",
+        "tx",
+        "amount",
+        "available",
+        "held",
+        "total",
+    ])?;
+
+    let balances = |balances: &Balances| {
+        [
+            balances.available.reduce().to_string(),
+            balances.held.reduce().to_string(),
+            balances.total().reduce().to_string(),
+        ]
+    };
+
+    let [available, held, total] = balances(&statement.opening);
+    let from = statement.from.to_string();
+    wtr.write_record(["opening", &from, "", "", &available, &held, &total])?;
+
+    for line in &statement.lines {
+        let [available, held, total] = balances(&line.balances);
+        wtr.write_record([
+            line.posting.kind.name(),
+            &line.posting.timestamp.to_string(),
+            &line.posting.tx.to_string(),
+            &line.posting.amount.reduce().to_string(),
+            &available,
+            &held,
+            &total,
+        ])?;
+    }
+
+    let [available, held, total] = balances(&statement.closing);
+    let to = statement.to.to_string();
+    wtr.write_record(["closing", &to, "", "", &available, &held, &total])?;
+
+    let summary = &statement.disputes;
+    let count = summary.disputes.to_string();
+    wtr.write_record(["disputes", "", "", &count, "", "", ""])?;
+    for (kind, amount) in [
+        ("disputed", summary.disputed),
+        ("resolved", summary.resolved),
+        ("charged_back", summary.charged_back),
+        ("reversed", summary.reversed),
+    ] {
+        wtr.write_record([kind, "", "", &amount.reduce().to_string(), "", "", ""])?;
+    }
+
+    wtr.flush()?;
+    Ok(())
+}
+
+pub fn write_statement_json(mut out: impl Write, statement: &Statement) -> Result<(), CliError> {
+    let balances = |balances: &Balances| {
+        serde_json::json!({
+            "available": balances.available.reduce().to_string(),
+            "held": balances.held.reduce().to_string(),
+            "total": balances.total().reduce().to_string(),
+        })
+    };
+
+    let postings: Vec<_> = statement
+        .lines
+        .iter()
+        .map(|line| {
+            serde_json::json!({
+                "type": line.posting.kind.name(),
+                "timestamp": line.posting.timestamp.to_string(),
+                "tx": line.posting.tx,
+                "amount": line.posting.amount.reduce().to_string(),
+                "balances": balances(&line.balances),
+            })
+        })
+        .collect();
+
+    let summary = &statement.disputes;
+    let json = serde_json::json!({
+        "client": statement.client,
+        "from": statement.from.to_string(),
+        "to": statement.to.to_string(),
+        "opening": balances(&statement.opening),
+        "postings": postings,
+        "closing": balances(&statement.closing),
+        "disputes": {
+            "count": summary.disputes,
+            "disputed": summary.disputed.reduce().to_string(),
+            "resolved": summary.resolved.reduce().to_string(),
+            "charged_back": summary.charged_back.reduce().to_string(),
+            "reversed": summary.reversed.reduce().to_string(),
+        },
+    });
+
+    serde_json::to_writer_pretty(&mut out, &json).map_err(io::Error::from)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+pub fn write_trial_balance(out: impl Write, trial_balance: &TrialBalance) -> Result<(), CliError> {
+    let mut wtr = csv::Writer::from_writer(out);
+    wtr.write_record(["account", "debits", "credits", "balance"])?;
+
+    for row in &trial_balance.rows {
+        wtr.write_record([
+            row.account.to_string(),
+            row.debits.reduce().to_string(),
+            row.credits.reduce().to_string(),
+            row.balance().reduce().to_string(),
+        ])?;
+    }
+
+    let (debits, credits) = (trial_balance.total_debits(), trial_balance.total_credits());
+    wtr.write_record([
+        "total".to_string(),
+        debits.reduce().to_string(),
+        credits.reduce().to_string(),
+        (debits - credits).reduce().to_string(),
+    ])?;
+
+    wtr.flush()?;
+    Ok(())
+}
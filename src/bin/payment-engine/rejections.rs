@@ -0,0 +1,126 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fmt::Display,
+    io::{self, Write},
+};
+
+use payment_engine::{Rejection, Severity, Transaction};
+
+use crate::{compress::Output, input::Location};
+
+/// Where the transactions that were rejected, ignored or applied out of order are
+/// reported, either one line per row or summarised by reason once processed.
+pub struct Rejections {
+    output: Output,
+    /// The counts by reason, when summarising instead of reporting each row.
+    summary: Option<Summary>,
+}
+
+#[derive(Default)]
+struct Summary {
+    rejections: HashMap<Rejection, u64>,
+    out_of_order: u64,
+    malformed: u64,
+}
+
+impl Rejections {
+    pub fn new(output: Output, summarize: bool) -> Self {
+        Self {
+            output,
+            summary: summarize.then(Summary::default),
+        }
+    }
+
+    /// Report a transaction applied although older than a previous one of its client.
+    pub fn out_of_order(&mut self, location: Location<'_>, tx: &Transaction) -> io::Result<()> {
+        match &mut self.summary {
+            Some(summary) => {
+                summary.out_of_order += 1;
+                Ok(())
+            }
+            None => writeln!(
+                self.output,
+                "{location}: transaction {} of client {} applied out of order",
+                tx.id(),
+                tx.client()
+            ),
+        }
+    }
+
+    /// Report a transaction that was rejected or ignored.
+    pub fn rejected(
+        &mut self,
+        location: Location<'_>,
+        tx: &Transaction,
+        rejection: Rejection,
+    ) -> io::Result<()> {
+        match &mut self.summary {
+            Some(summary) => {
+                *summary.rejections.entry(rejection).or_default() += 1;
+                Ok(())
+            }
+            None => writeln!(
+                self.output,
+                "{location}: transaction {} of client {} {}: {rejection}",
+                tx.id(),
+                tx.client(),
+                outcome(rejection)
+            ),
+        }
+    }
+
+    /// Report a row that couldn't be parsed.
+    pub fn malformed(&mut self, err: impl Display) -> io::Result<()> {
+        match &mut self.summary {
+            Some(summary) => {
+                summary.malformed += 1;
+                Ok(())
+            }
+            None => writeln!(self.output, "{err}"),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    /// Write the summary if summarising, most frequent reason first, and complete the
+    /// output.
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(summary) = self.summary.take() {
+            let mut rejections: Vec<_> = summary.rejections.into_iter().collect();
+            rejections
+                .sort_by_cached_key(|&(rejection, count)| (Reverse(count), rejection.to_string()));
+            for (rejection, count) in rejections {
+                writeln!(
+                    self.output,
+                    "{count} transactions {}: {rejection}",
+                    outcome(rejection)
+                )?;
+            }
+            if summary.out_of_order > 0 {
+                writeln!(
+                    self.output,
+                    "{} transactions applied out of order",
+                    summary.out_of_order
+                )?;
+            }
+            if summary.malformed > 0 {
+                writeln!(self.output, "{} malformed rows", summary.malformed)?;
+            }
+        }
+        self.output.finish()
+    }
+}
+
+/// How the transaction was turned down.
+fn outcome(rejection: Rejection) -> &'static str {
+    if rejection == Rejection::Replayed {
+        "ignored"
+    } else if rejection.severity() == Severity::High {
+        "rejected as anomaly"
+    } else {
+        "rejected"
+    }
+}
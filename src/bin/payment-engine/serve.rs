@@ -0,0 +1,183 @@
+use std::io::{Cursor, Read};
+
+use payment_engine::{AccountReport, Engine, Histogram, Rejection, Schema};
+use tiny_http::{Header, Method, Request, Server, StatusCode};
+
+use crate::{
+    CliError, Tally, check_anomalies,
+    config::Settings,
+    input::{self, Input, Location},
+    process_transaction,
+    rejections::Rejections,
+    verify,
+};
+
+type Response = tiny_http::Response<Cursor<Vec<u8>>>;
+
+/// Buckets of batch sizes, in transactions.
+const BATCH_SIZES: [f64; 7] = [1.0, 10.0, 100.0, 1e3, 1e4, 1e5, 1e6];
+
+/// An engine kept running to process batches of transactions posted over HTTP.
+///
+/// Requests are handled one at a time, in order of arrival:
+///
+/// - `POST /transactions` processes a CSV batch, refused as a whole if malformed;
+/// - `GET /metrics` exports the engine's statistics in the Prometheus text format;
+/// - `GET /accounts` writes the accounts with the default columns.
+///
+/// With `--verify`, the whole engine is reconciled after every batch, which takes time
+/// proportional to every transaction processed so far rather than to the batch.
+pub struct Service<'a> {
+    engine: Engine,
+    settings: Settings,
+    schema: Schema,
+    /// Largest batch accepted, in bytes.
+    max_body_bytes: u64,
+    rejections: &'a mut Rejections,
+    batches: u64,
+    batch_sizes: Histogram,
+}
+
+impl<'a> Service<'a> {
+    pub fn new(
+        settings: Settings,
+        schema: Schema,
+        max_body_bytes: u64,
+        rejections: &'a mut Rejections,
+    ) -> Result<Self, CliError> {
+        Ok(Self {
+            engine: settings.engine()?.with_timing(),
+            settings,
+            schema,
+            max_body_bytes,
+            rejections,
+            batches: 0,
+            batch_sizes: Histogram::new(BATCH_SIZES),
+        })
+    }
+
+    /// Serve requests on the address until an error stops the service.
+    ///
+    /// Malformed batches only fail their request, while errors the CLI would exit with
+    /// otherwise, such as discrepancies found by `--verify`, stop the service once
+    /// the request is answered.
+    pub fn run(mut self, listen: &str) -> Result<(), CliError> {
+        let server =
+            Server::http(listen).map_err(|err| CliError::Io(format!("{listen}: {err}")))?;
+        eprintln!("listening on http://{}", server.server_addr());
+
+        for mut request in server.incoming_requests() {
+            let (response, result) = match self.handle(&mut request) {
+                Ok(response) => (response, Ok(())),
+                Err(err) => (text(500, err.to_string()), Err(err)),
+            };
+            // A client gone before the response only affects its own request.
+            let _ = request.respond(response);
+            result?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, request: &mut Request) -> Result<Response, CliError> {
+        let path = request.url().split('?').next().unwrap_or_default();
+        match (request.method(), path) {
+            (Method::Post, "/transactions") => self.process_batch(request),
+            (Method::Get, "/metrics") => {
+                let mut body = vec![];
+                self.engine.stats().write_prometheus(&mut body)?;
+                self.batch_sizes.write_prometheus(
+                    &mut body,
+                    "payment_engine_batch_size",
+                    "Transactions in each batch posted to the service.",
+                )?;
+                Ok(response(200, body, "text/plain; version=0.0.4"))
+            }
+            (Method::Get, "/accounts") => {
+                let mut body = vec![];
+                AccountReport::new(&self.engine).write_csv(&mut body)?;
+                Ok(response(200, body, "text/csv"))
+            }
+            (_, "/transactions" | "/metrics" | "/accounts") => {
+                Ok(text(405, "method not allowed".to_string()))
+            }
+            _ => Ok(text(404, "not found".to_string())),
+        }
+    }
+
+    fn process_batch(&mut self, request: &mut Request) -> Result<Response, CliError> {
+        // The announced length is checked first, so that oversized batches are refused
+        // without reading them, and the body is still cut in case it lied.
+        let too_large = || text(413, format!("batch over {} bytes", self.max_body_bytes));
+        if request
+            .body_length()
+            .is_some_and(|length| length as u64 > self.max_body_bytes)
+        {
+            return Ok(too_large());
+        }
+        let mut body = vec![];
+        let mut reader = request.as_reader().take(self.max_body_bytes + 1);
+        if let Err(err) = reader.read_to_end(&mut body) {
+            return Ok(text(400, err.to_string()));
+        }
+        if body.len() as u64 > self.max_body_bytes {
+            return Ok(too_large());
+        }
+        self.batches += 1;
+        let batch = Input::Batch(self.batches, body);
+
+        // Every row is read before processing any, so that a malformed batch can be
+        // posted again once fixed.
+        let mut txs = vec![];
+        let inputs = std::slice::from_ref(&batch);
+        let read = input::read_transactions(inputs, &self.schema, |location, tx| {
+            txs.push((location.line, tx));
+            Ok(())
+        });
+        if let Err(CliError::Parse(err) | CliError::Io(err)) = read {
+            return Ok(text(400, err));
+        }
+        read?;
+
+        let mut tally = Tally::default();
+        let mut ignored = 0;
+        for &(line, tx) in &txs {
+            let location = Location {
+                input: &batch,
+                line,
+            };
+            let result = process_transaction(&mut self.engine, location, tx, self.rejections)?;
+            tally.record(result);
+            ignored += (result == Err(Rejection::Replayed)) as usize;
+        }
+        self.rejections.flush()?;
+        self.batch_sizes.observe(txs.len() as f64);
+
+        verify(&self.engine, &self.settings)?;
+        check_anomalies(&self.settings, tally.anomalies)?;
+
+        let json = serde_json::json!({
+            "batch": self.batches,
+            "rows": txs.len(),
+            "rejected": tally.rejected,
+            "ignored": ignored,
+            "anomalies": tally.anomalies,
+        });
+        Ok(response(
+            200,
+            json.to_string().into_bytes(),
+            "application/json",
+        ))
+    }
+}
+
+fn response(status: u16, body: Vec<u8>, content_type: &str) -> Response {
+    let header = Header::from_bytes("Content-Type", content_type).unwrap();
+    tiny_http::Response::from_data(body)
+        .with_status_code(StatusCode(status))
+        .with_header(header)
+}
+
+fn text(status: u16, mut message: String) -> Response {
+    message.push('\n');
+    response(status, message.into_bytes(), "text/plain")
+}
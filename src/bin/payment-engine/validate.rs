@@ -0,0 +1,141 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+};
+
+use fastnum::D256;
+use payment_engine::{Engine, PostingKind, Rejection, Severity, Transaction, TransactionKind};
+
+use crate::CliError;
+
+/// What processing the rows of the inputs did, for `validate`.
+#[derive(Default)]
+pub struct Validation {
+    malformed: usize,
+    /// Rows ignored as copies of already processed ones.
+    replayed: usize,
+    /// Rejections of high severity, counted in `counts` too.
+    anomalies: usize,
+    /// Accepted and rejected transactions of each type, indexed like [`TransactionKind::ALL`].
+    counts: [(usize, usize); TransactionKind::ALL.len()],
+    rejections: BTreeMap<String, usize>,
+    /// Clients of the rows processed, accepted or not.
+    clients: BTreeSet<u16>,
+}
+
+/// The funds that entered and left a client's account.
+#[derive(Default)]
+struct Flow {
+    deposits: D256,
+    withdrawals: D256,
+    /// Funds charged back, net of chargeback reversals.
+    chargebacks: D256,
+}
+
+impl Flow {
+    /// The flow of the client's postings in the engine, including disputes charged
+    /// back on expiry.
+    fn new(engine: &Engine, client: u16) -> Self {
+        let mut flow = Self::default();
+        for entry in engine.journal(client) {
+            match entry.kind {
+                PostingKind::Deposit => flow.deposits += entry.amount,
+                PostingKind::Withdrawal => flow.withdrawals += entry.amount,
+                PostingKind::Chargeback => flow.chargebacks += entry.amount,
+                PostingKind::ChargebackReversal => flow.chargebacks -= entry.amount,
+                PostingKind::Hold | PostingKind::Release => {}
+            }
+        }
+        flow
+    }
+
+    fn net(&self) -> D256 {
+        self.deposits - self.withdrawals - self.chargebacks
+    }
+}
+
+impl Validation {
+    pub fn record(&mut self, tx: &Transaction, result: Result<(), Rejection>) {
+        let index = TransactionKind::ALL
+            .iter()
+            .position(|&kind| kind == tx.kind())
+            .unwrap();
+        self.clients.insert(tx.client());
+        match result {
+            Ok(()) => self.counts[index].0 += 1,
+            Err(Rejection::Replayed) => self.replayed += 1,
+            Err(rejection) => {
+                self.counts[index].1 += 1;
+                self.anomalies += (rejection.severity() == Severity::High) as usize;
+                *self.rejections.entry(rejection.to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn record_malformed(&mut self) {
+        self.malformed += 1;
+    }
+
+    pub fn malformed(&self) -> usize {
+        self.malformed
+    }
+
+    pub fn anomalies(&self) -> usize {
+        self.anomalies
+    }
+
+    pub fn rejected(&self) -> usize {
+        self.counts.iter().map(|(_, rejected)| rejected).sum()
+    }
+
+    /// Write the report as JSON, with the accounts the engine locked but none of its
+    /// balances.
+    pub fn write_json(&self, mut out: impl Write, engine: &Engine) -> Result<(), CliError> {
+        let accepted: usize = self.counts.iter().map(|(accepted, _)| accepted).sum();
+        let types: serde_json::Map<_, _> = TransactionKind::ALL
+            .iter()
+            .zip(&self.counts)
+            .map(|(kind, (accepted, rejected))| {
+                let counts = serde_json::json!({ "accepted": accepted, "rejected": rejected });
+                (kind.name().to_string(), counts)
+            })
+            .collect();
+        let locked: Vec<_> = engine
+            .accounts()
+            .filter(|(_, account)| account.is_locked())
+            .map(|(client, _)| client)
+            .collect();
+        let flows: Vec<_> = self
+            .clients
+            .iter()
+            .map(|&client| {
+                let flow = Flow::new(engine, client);
+                serde_json::json!({
+                    "client": client,
+                    "deposits": flow.deposits.reduce().to_string(),
+                    "withdrawals": flow.withdrawals.reduce().to_string(),
+                    "chargebacks": flow.chargebacks.reduce().to_string(),
+                    "net": flow.net().reduce().to_string(),
+                })
+            })
+            .collect();
+
+        let json = serde_json::json!({
+            "rows": self.malformed + self.replayed + accepted + self.rejected(),
+            "malformed": self.malformed,
+            "replayed": self.replayed,
+            "accepted": accepted,
+            "rejected": self.rejected(),
+            "anomalies": self.anomalies,
+            "types": types,
+            "rejections": self.rejections,
+            "clients": self.clients.len(),
+            "locked_clients": locked,
+            "net_flows": flows,
+        });
+
+        serde_json::to_writer_pretty(&mut out, &json).map_err(io::Error::from)?;
+        writeln!(out)?;
+        Ok(())
+    }
+}
@@ -1,12 +1,39 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
-use crate::{Transaction, account::Account, transaction::TxPayload};
+use crate::{
+    Amount, Rejection, Timestamp, Transaction,
+    account::Account,
+    dispute::{Dispute, DisputePolicy, DisputeState, DisputeTransition, Disputes, ExpiryAction},
+    history::{DisputeRecord, HistoryEntry, TransactionRecord, TxStatus},
+    limits::{LimitTracker, Limits},
+    statement::{Posting, PostingKind},
+    stats::table_size,
+    transaction::TxPayload,
+};
 
 #[derive(Debug, Default)]
 pub(crate) struct Client {
     account: Account,
     disputes: Disputes,
+    limits: LimitTracker,
+    /// Accepted deposits and withdrawals, always timestamped.
     txs: HashMap<u32, Transaction>,
+    /// Every transaction processed for this client, in processing order.
+    history: Vec<HistoryEntry>,
+    /// Disputes closed on expiry, which are not part of the history, in order.
+    expiries: Vec<Expiry>,
+    /// The latest input timestamp seen for this client.
+    last_seen: Option<Timestamp>,
+}
+
+/// A dispute closed by the engine once past its deadline.
+#[derive(Debug, Clone, Copy)]
+struct Expiry {
+    /// The length of the history when the dispute expired.
+    position: usize,
+    tx: u32,
+    action: ExpiryAction,
+    at: Timestamp,
 }
 
 impl Client {
@@ -14,97 +41,515 @@ impl Client {
         &self.account
     }
 
-    pub(super) fn process_transaction(&mut self, tx: Transaction) {
-        if self.account.is_locked() {
-            return;
+    /// Every transaction processed for this client, in processing order.
+    pub(crate) fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    /// The transaction at the given position of the history, with its current status.
+    pub(crate) fn record(&self, position: usize) -> TransactionRecord<'_> {
+        let entry = &self.history[position];
+        let status = match entry.outcome {
+            Err(rejection) => TxStatus::Rejected(rejection),
+            Ok(()) if entry.tx.deposited_amount().is_some() => self
+                .disputes
+                .state(entry.tx.id)
+                .map_or(TxStatus::Accepted, TxStatus::Disputed),
+            Ok(()) => TxStatus::Accepted,
+        };
+
+        TransactionRecord {
+            tx: &entry.tx,
+            status,
         }
+    }
 
-        match tx.payload {
+    /// Every change to the account's balances, in processing order.
+    ///
+    /// Postings are not stored but derived from the accepted transactions of the
+    /// history and the disputes closed on expiry, replaying the disputes' lifecycle to
+    /// find the amounts moved.
+    pub(crate) fn postings(&self) -> Vec<Posting> {
+        let mut disputes = Disputes::default();
+        let mut postings = vec![];
+        let mut expiries = self.expiries.iter().peekable();
+        for (position, entry) in self.history.iter().enumerate() {
+            while let Some(expiry) = expiries.next_if(|expiry| expiry.position == position) {
+                postings.extend(self.replay_expiry(&mut disputes, expiry));
+            }
+            if entry.outcome.is_ok() {
+                let at = entry.tx.timestamp.unwrap_or_default();
+                postings.extend(self.replay(&mut disputes, entry.tx.id, entry.tx.payload, at));
+            }
+        }
+        for expiry in expiries {
+            postings.extend(self.replay_expiry(&mut disputes, expiry));
+        }
+        postings
+    }
+
+    fn replay_expiry(&self, disputes: &mut Disputes, expiry: &Expiry) -> Option<Posting> {
+        let payload = match expiry.action {
+            ExpiryAction::Resolve => TxPayload::Resolve { amount: None },
+            ExpiryAction::Chargeback => TxPayload::Chargeback { amount: None },
+        };
+        self.replay(disputes, expiry.tx, payload, expiry.at)
+    }
+
+    /// The posting of an accepted transaction, given the disputes as they were before
+    /// it, which are updated like when it was processed.
+    fn replay(
+        &self,
+        disputes: &mut Disputes,
+        id: u32,
+        payload: TxPayload,
+        at: Timestamp,
+    ) -> Option<Posting> {
+        let (kind, amount) = match payload {
+            TxPayload::Deposit { amount } => (PostingKind::Deposit, amount),
+            TxPayload::Withdrawal { amount } => (PostingKind::Withdrawal, amount),
+            TxPayload::Dispute { .. } if disputes.state(id) == Some(DisputeState::ChargedBack) => {
+                disputes.represent(id, at);
+                return None;
+            }
+            TxPayload::Dispute { amount } => {
+                let amount = amount.unwrap_or_else(|| {
+                    let deposited = self.txs[&id].deposited_amount().unwrap_or_default();
+                    deposited
+                        - disputes
+                            .get(id)
+                            .map_or(Amount::ZERO, Dispute::disputed_amount)
+                });
+                disputes.hold(id, amount, at);
+                (PostingKind::Hold, amount)
+            }
+            TxPayload::Resolve { amount } => {
+                let dispute = disputes.get_mut(id)?;
+                let (_, closed) = dispute.closing(DisputeTransition::Resolve)?;
+                if dispute.represented {
+                    let amount = amount.unwrap_or(dispute.charged_back);
+                    dispute.reverse_chargeback(amount, closed);
+                    (PostingKind::ChargebackReversal, amount)
+                } else {
+                    let amount = amount.unwrap_or(dispute.held);
+                    dispute.release(amount, closed);
+                    (PostingKind::Release, amount)
+                }
+            }
+            TxPayload::Chargeback { amount } => {
+                let dispute = disputes.get_mut(id)?;
+                let (_, closed) = dispute.closing(DisputeTransition::Chargeback)?;
+                if dispute.represented {
+                    dispute.state = closed;
+                    return None;
+                }
+                let amount = amount.unwrap_or(dispute.held);
+                dispute.charge_back(amount, closed);
+                (PostingKind::Chargeback, amount)
+            }
+        };
+
+        Some(posting(at, id, kind, amount))
+    }
+
+    /// Estimate of the memory allocated for the client's state.
+    pub(crate) fn heap_size(&self) -> usize {
+        self.disputes.heap_size()
+            + self.limits.heap_size()
+            + table_size::<(u32, Transaction)>(self.txs.capacity())
+            + self.history.capacity() * size_of::<HistoryEntry>()
+            + self.expiries.capacity() * size_of::<Expiry>()
+    }
+
+    /// The sum of the funds held by the client's disputes.
+    pub(crate) fn disputes_held(&self) -> Amount {
+        self.disputes
+            .iter()
+            .fold(Amount::ZERO, |held, (_, dispute)| held + dispute.held)
+    }
+
+    pub(crate) fn dispute_state(&self, id: u32) -> Option<DisputeState> {
+        self.disputes.state(id)
+    }
+
+    pub(crate) fn open_dispute_count(&self) -> usize {
+        self.disputes
+            .iter()
+            .filter(|(_, dispute)| dispute.state.is_open())
+            .count()
+    }
+
+    /// Disputes pending a resolution, oldest first.
+    pub(crate) fn open_disputes(&self) -> Vec<DisputeRecord<'_>> {
+        let mut open: Vec<_> = self
+            .disputes
+            .iter()
+            .filter(|(_, dispute)| dispute.state.is_open())
+            .map(|(id, dispute)| DisputeRecord {
+                tx: &self.txs[&id],
+                dispute,
+            })
+            .collect();
+        open.sort_by_key(|record| (record.opened_at(), record.tx.id));
+        open
+    }
+
+    /// Process a transaction that happened at `now`, recording it in the history.
+    ///
+    /// `now` is the transaction's own timestamp, if any, or the engine's clock time.
+    /// Returns the change to the account's balances, if any.
+    pub(super) fn post_transaction(
+        &mut self,
+        tx: Transaction,
+        limits: &Limits,
+        policy: &DisputePolicy,
+        now: Timestamp,
+    ) -> Result<Option<Posting>, Rejection> {
+        let result = self.apply(tx, limits, policy, now);
+        self.record_outcome(tx, now, result.map(drop));
+        result
+    }
+
+    /// Add a transaction to the history with its outcome, e.g. when rejected before
+    /// being processed by the client.
+    pub(super) fn record_outcome(
+        &mut self,
+        tx: Transaction,
+        now: Timestamp,
+        outcome: Result<(), Rejection>,
+    ) {
+        self.history.push(HistoryEntry {
+            tx: Transaction {
+                timestamp: Some(now),
+                ..tx
+            },
+            outcome,
+        });
+    }
+
+    /// Whether the transaction's input timestamp is older than the latest one seen for
+    /// the client.
+    pub(crate) fn is_out_of_order(&self, tx: &Transaction) -> bool {
+        tx.timestamp
+            .is_some_and(|timestamp| self.last_seen.is_some_and(|last| timestamp < last))
+    }
+
+    /// Check that a deposit or withdrawal doesn't reuse the ID of one of the client's
+    /// transactions.
+    ///
+    /// Reusing it with another type or amount is a conflict rather than a mere duplicate,
+    /// as the transaction can't be a copy sent again.
+    fn check_new_id(&self, tx: &Transaction) -> Result<(), Rejection> {
+        match self.txs.get(&tx.id) {
+            None => Ok(()),
+            Some(existing) if existing.kind() == tx.kind() && existing.amount() == tx.amount() => {
+                Err(Rejection::DuplicateTransaction)
+            }
+            Some(_) => Err(Rejection::ConflictingTransaction),
+        }
+    }
+
+    fn apply(
+        &mut self,
+        mut tx: Transaction,
+        limits: &Limits,
+        policy: &DisputePolicy,
+        now: Timestamp,
+    ) -> Result<Option<Posting>, Rejection> {
+        // Only input timestamps are tracked, the engine's clock is trusted.
+        if let Some(timestamp) = tx.timestamp {
+            self.last_seen = Some(self.last_seen.map_or(timestamp, |last| last.max(timestamp)));
+        }
+        tx.timestamp = Some(now);
+
+        if self.account.is_locked() && !self.is_representment(&tx, policy) {
+            return Err(Rejection::AccountLocked);
+        }
+
+        self.limits.check_velocity(limits, now)?;
+
+        let posting = match tx.payload {
             TxPayload::Deposit { amount } => {
-                if self.txs.contains_key(&tx.id) {
-                    return;
+                self.check_new_id(&tx)?;
+                if amount <= Amount::ZERO {
+                    return Err(Rejection::InvalidAmount);
                 }
 
-                self.account.deposit(amount);
+                self.account.deposit(amount)?;
                 self.txs.insert(tx.id, tx);
+                Some(posting(now, tx.id, PostingKind::Deposit, amount))
             }
             TxPayload::Withdrawal { amount } => {
-                if self.txs.contains_key(&tx.id) {
-                    return;
+                self.check_new_id(&tx)?;
+                if amount <= Amount::ZERO {
+                    return Err(Rejection::InvalidAmount);
                 }
 
-                if self.account.withdraw(amount).is_ok() {
-                    self.txs.insert(tx.id, tx);
-                }
+                self.limits.check_withdrawal(limits, amount, now)?;
+                self.account.withdraw(amount)?;
+                self.limits.record_withdrawal(amount, now);
+                self.txs.insert(tx.id, tx);
+                Some(posting(now, tx.id, PostingKind::Withdrawal, amount))
             }
-            TxPayload::Dispute => {
-                if !self.disputes.is_disputed(tx.id)
-                    && let Some(original_tx) = self.txs.get(&tx.id)
-                    && let Some(amount) = original_tx.deposited_amount()
-                {
-                    if self.account.hold_funds(amount).is_ok() {
-                        self.disputes.dispute(tx.id);
-                    }
-                }
+            TxPayload::Dispute { amount } => self.dispute(tx.id, amount, limits, policy, now)?,
+            TxPayload::Resolve { amount } => self.resolve(tx.id, amount, policy, now)?,
+            TxPayload::Chargeback { amount } => self.chargeback(tx.id, amount, policy, now)?,
+        };
+
+        self.limits.record(limits, now);
+
+        Ok(posting)
+    }
+
+    /// Apply the policy's expiry action at `now` to the dispute of the given transaction
+    /// opened at `opened_at`.
+    ///
+    /// Returns the change to the account's balances, if the dispute was still open.
+    /// Disputes of locked accounts are left open.
+    pub(super) fn expire_dispute(
+        &mut self,
+        id: u32,
+        opened_at: Timestamp,
+        policy: &DisputePolicy,
+        now: Timestamp,
+    ) -> Option<Posting> {
+        let still_open = self
+            .disputes
+            .get(id)
+            .is_some_and(|dispute| dispute.state.is_open() && dispute.opened_at == opened_at);
+        if self.account.is_locked() || !still_open {
+            return None;
+        }
+
+        let result = match policy.on_expiry {
+            ExpiryAction::Resolve => self.resolve(id, None, policy, now),
+            ExpiryAction::Chargeback => self.chargeback(id, None, policy, now),
+        };
+        if result.is_ok() {
+            self.expiries.push(Expiry {
+                position: self.history.len(),
+                tx: id,
+                action: policy.on_expiry,
+                at: now,
+            });
+        }
+
+        result.ok().flatten()
+    }
+
+    /// Whether `tx` is part of contesting a chargeback, which is allowed on locked
+    /// accounts.
+    fn is_representment(&self, tx: &Transaction, policy: &DisputePolicy) -> bool {
+        if !policy.transitions.allows(DisputeTransition::Represent) {
+            return false;
+        }
+
+        let Some(dispute) = self.disputes.get(tx.id) else {
+            return false;
+        };
+
+        match tx.payload {
+            TxPayload::Dispute { .. } => dispute.state == DisputeState::ChargedBack,
+            TxPayload::Resolve { .. } | TxPayload::Chargeback { .. } => {
+                dispute.state == DisputeState::Reopened && dispute.represented
             }
-            TxPayload::Resolve => {
-                if let Some(original_tx) = self.disputed_transaction(tx.id)
-                    && let Some(amount) = original_tx.deposited_amount()
-                {
-                    self.account.release_funds(amount);
-                    self.disputes.resolve(tx.id);
-                }
+            TxPayload::Deposit { .. } | TxPayload::Withdrawal { .. } => false,
+        }
+    }
+
+    /// Dispute `amount` of the given transaction, or everything not yet disputed.
+    fn dispute(
+        &mut self,
+        id: u32,
+        amount: Option<Amount>,
+        limits: &Limits,
+        policy: &DisputePolicy,
+        now: Timestamp,
+    ) -> Result<Option<Posting>, Rejection> {
+        let original_tx = self.txs.get(&id).ok_or(Rejection::UnknownTransaction)?;
+        let deposited = original_tx
+            .deposited_amount()
+            .ok_or(Rejection::NotDisputable)?;
+
+        if let Some(window) = policy.window
+            && original_tx
+                .timestamp
+                .is_some_and(|deposited_at| now > deposited_at.saturating_add(window))
+        {
+            return Err(Rejection::DisputeWindowExpired);
+        }
+
+        let transitions = policy.transitions;
+        match self.disputes.get(id).map(|d| (d.state, d.represented)) {
+            None | Some((DisputeState::Open, _)) | Some((DisputeState::Reopened, false)) => {}
+            Some((DisputeState::Reopened, true)) => return Err(Rejection::AlreadyDisputed),
+            Some((DisputeState::Resolved, _)) if transitions.allows(DisputeTransition::Reopen) => {}
+            Some((DisputeState::ChargedBack, _))
+                if transitions.allows(DisputeTransition::Represent) =>
+            {
+                self.disputes.represent(id, now);
+                return Ok(None);
             }
-            TxPayload::Chargeback => {
-                if let Some(original_tx) = self.disputed_transaction(tx.id)
-                    && let Some(amount) = original_tx.deposited_amount()
-                {
-                    self.account.chargeback(amount);
-                    self.disputes.chargeback(tx.id);
-                }
+            Some((
+                DisputeState::Resolved | DisputeState::ChargedBack | DisputeState::Arbitrated,
+                _,
+            )) => return Err(Rejection::InvalidDisputeTransition),
+        };
+
+        let disputed = self
+            .disputes
+            .get(id)
+            .map_or(Amount::ZERO, |dispute| dispute.disputed_amount());
+        let undisputed = deposited - disputed;
+        let amount = match amount {
+            None if undisputed.is_zero() => return Err(Rejection::AlreadyDisputed),
+            None => undisputed,
+            Some(amount) if amount <= Amount::ZERO || amount > undisputed => {
+                return Err(Rejection::InvalidAmount);
             }
+            Some(amount) => amount,
+        };
+
+        LimitTracker::check_held(limits, self.account.held_funds() + amount)?;
+        if policy.allow_deficit {
+            self.account.hold_funds_allowing_deficit(amount)?;
+        } else {
+            self.account.hold_funds(amount)?;
         }
-    }
 
-    fn disputed_transaction(&self, id: u32) -> Option<&Transaction> {
-        self.txs.get(&id).filter(|_| self.disputes.is_disputed(id))
+        self.disputes.hold(id, amount, now);
+        Ok(Some(posting(now, id, PostingKind::Hold, amount)))
     }
-}
 
-/// Tracks disputed transactions for a client.
-#[derive(Debug, Default)]
-struct Disputes {
-    txs: HashSet<u32>,
-    chargebacks: HashSet<u32>,
-}
+    /// Release `amount` of the funds held by the dispute of the given transaction,
+    /// or all of them.
+    fn resolve(
+        &mut self,
+        id: u32,
+        amount: Option<Amount>,
+        policy: &DisputePolicy,
+        now: Timestamp,
+    ) -> Result<Option<Posting>, Rejection> {
+        let closed = self.closed_state(id, DisputeTransition::Resolve, policy)?;
+        let dispute = self.disputes.get_mut(id).unwrap();
+
+        if dispute.represented {
+            // The client won the arbitration of the chargeback.
+            let amount = checked_amount(amount, dispute.charged_back)?;
+            self.account.reverse_chargeback(amount)?;
+            dispute.reverse_chargeback(amount, closed);
+            return Ok(Some(posting(
+                now,
+                id,
+                PostingKind::ChargebackReversal,
+                amount,
+            )));
+        }
 
-impl Disputes {
-    fn is_disputed(&self, tx: u32) -> bool {
-        self.txs.contains(&tx) || self.chargebacks.contains(&tx)
+        let amount = checked_amount(amount, dispute.held)?;
+        self.account.release_funds(amount)?;
+        dispute.release(amount, closed);
+        Ok(Some(posting(now, id, PostingKind::Release, amount)))
     }
 
-    fn dispute(&mut self, tx: u32) -> bool {
-        self.txs.insert(tx)
+    /// Charge back `amount` of the funds held by the dispute of the given transaction,
+    /// or all of them.
+    fn chargeback(
+        &mut self,
+        id: u32,
+        amount: Option<Amount>,
+        policy: &DisputePolicy,
+        now: Timestamp,
+    ) -> Result<Option<Posting>, Rejection> {
+        let closed = self.closed_state(id, DisputeTransition::Chargeback, policy)?;
+        let dispute = self.disputes.get_mut(id).unwrap();
+
+        if dispute.represented {
+            // The client lost the arbitration, the chargeback stands.
+            dispute.state = closed;
+            return Ok(None);
+        }
+
+        let amount = checked_amount(amount, dispute.held)?;
+        self.account.chargeback(amount)?;
+        dispute.charge_back(amount, closed);
+        Ok(Some(posting(now, id, PostingKind::Chargeback, amount)))
     }
 
-    fn resolve(&mut self, tx: u32) -> bool {
-        self.txs.remove(&tx)
+    /// The state the dispute of the given transaction moves to once closed by
+    /// `transition`, if the dispute is open and the transition allowed.
+    fn closed_state(
+        &self,
+        id: u32,
+        transition: DisputeTransition,
+        policy: &DisputePolicy,
+    ) -> Result<DisputeState, Rejection> {
+        if !self.txs.contains_key(&id) {
+            return Err(Rejection::UnknownTransaction);
+        }
+
+        let (transition, closed) = self
+            .disputes
+            .get(id)
+            .and_then(|dispute| dispute.closing(transition))
+            .ok_or(Rejection::NotDisputed)?;
+
+        if policy.transitions.allows(transition) {
+            Ok(closed)
+        } else {
+            Err(Rejection::InvalidDisputeTransition)
+        }
     }
+}
 
-    fn chargeback(&mut self, tx: u32) -> bool {
-        let found = self.txs.remove(&tx);
+fn posting(timestamp: Timestamp, tx: u32, kind: PostingKind, amount: Amount) -> Posting {
+    Posting {
+        timestamp,
+        tx,
+        kind,
+        amount,
+    }
+}
 
-        if found {
-            self.chargebacks.insert(tx);
+/// Validate `amount` against the `available` amount, defaulting to all of it.
+fn checked_amount(amount: Option<Amount>, available: Amount) -> Result<Amount, Rejection> {
+    match amount {
+        None => Ok(available),
+        Some(amount) if amount <= Amount::ZERO || amount > available => {
+            Err(Rejection::InvalidAmount)
         }
+        Some(amount) => Ok(amount),
+    }
+}
+
+#[cfg(test)]
+impl Client {
+    fn process_transaction(
+        &mut self,
+        tx: Transaction,
+        limits: &Limits,
+        policy: &DisputePolicy,
+        now: Timestamp,
+    ) -> Result<(), Rejection> {
+        self.post_transaction(tx, limits, policy, now).map(drop)
+    }
 
-        found
+    fn process(&mut self, tx: Transaction) -> Result<(), Rejection> {
+        self.process_transaction(
+            tx,
+            &Limits::default(),
+            &DisputePolicy::default(),
+            Timestamp::UNIX_EPOCH,
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use super::*;
+    use crate::{DisputeTransitions, Limit, limits::RateLimit};
 
     #[test]
     fn test_withdrawal() {
@@ -112,23 +557,26 @@ mod tests {
             Transaction {
                 id: 1,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Deposit { amount: 10.into() },
             },
             Transaction {
                 id: 2,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Withdrawal { amount: 5.into() },
             },
             Transaction {
                 id: 3,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Withdrawal { amount: 15.into() },
             },
         ];
 
         let mut client = Client::default();
         for tx in &txs {
-            client.process_transaction(*tx);
+            let _ = client.process(*tx);
         }
 
         assert_eq!(client.account.total_funds(), 5.into());
@@ -144,23 +592,26 @@ mod tests {
             Transaction {
                 id: 1,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Deposit { amount: 10.into() },
             },
             Transaction {
                 id: 1,
                 client: 1,
-                payload: TxPayload::Dispute,
+                timestamp: None,
+                payload: TxPayload::Dispute { amount: None },
             },
             Transaction {
                 id: 1,
                 client: 1,
-                payload: TxPayload::Resolve,
+                timestamp: None,
+                payload: TxPayload::Resolve { amount: None },
             },
         ];
 
         let mut client = Client::default();
         for tx in &txs {
-            client.process_transaction(*tx);
+            let _ = client.process(*tx);
         }
 
         assert_eq!(client.account.total_funds(), 10.into());
@@ -175,23 +626,26 @@ mod tests {
             Transaction {
                 id: 1,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Deposit { amount: 10.into() },
             },
             Transaction {
                 id: 1,
                 client: 1,
-                payload: TxPayload::Dispute,
+                timestamp: None,
+                payload: TxPayload::Dispute { amount: None },
             },
             Transaction {
                 id: 1,
                 client: 1,
-                payload: TxPayload::Chargeback,
+                timestamp: None,
+                payload: TxPayload::Chargeback { amount: None },
             },
         ];
 
         let mut client = Client::default();
         for tx in &txs {
-            client.process_transaction(*tx);
+            let _ = client.process(*tx);
         }
 
         assert_eq!(client.account.total_funds(), 0.into());
@@ -206,19 +660,21 @@ mod tests {
             Transaction {
                 id: 1,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Deposit { amount: 10.into() },
             },
             Transaction {
                 id: 2,
                 client: 1,
-                payload: TxPayload::Dispute,
+                timestamp: None,
+                payload: TxPayload::Dispute { amount: None },
             },
         ];
 
         let mut client = Client::default();
 
         for tx in &txs {
-            client.process_transaction(*tx);
+            let _ = client.process(*tx);
         }
 
         assert_eq!(client.account.total_funds(), 10.into());
@@ -234,24 +690,27 @@ mod tests {
             Transaction {
                 id: 1,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Deposit { amount: 10.into() },
             },
             Transaction {
                 id: 2,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Withdrawal { amount: 5.into() },
             },
             Transaction {
                 id: 2,
                 client: 1,
-                payload: TxPayload::Dispute,
+                timestamp: None,
+                payload: TxPayload::Dispute { amount: None },
             },
         ];
 
         let mut client = Client::default();
 
         for tx in &txs {
-            client.process_transaction(*tx);
+            let _ = client.process(*tx);
         }
 
         assert_eq!(client.account.total_funds(), 5.into());
@@ -267,24 +726,27 @@ mod tests {
             Transaction {
                 id: 1,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Deposit { amount: 10.into() },
             },
             Transaction {
                 id: 2,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Withdrawal { amount: 5.into() },
             },
             Transaction {
                 id: 1,
                 client: 1,
-                payload: TxPayload::Dispute,
+                timestamp: None,
+                payload: TxPayload::Dispute { amount: None },
             },
         ];
 
         let mut client = Client::default();
 
         for tx in &txs {
-            client.process_transaction(*tx);
+            let _ = client.process(*tx);
         }
 
         assert_eq!(client.account.total_funds(), 5.into());
@@ -294,47 +756,731 @@ mod tests {
         assert!(client.txs.contains_key(&2));
     }
 
+    #[test]
+    fn test_dispute_with_deficit() {
+        let policy = DisputePolicy {
+            allow_deficit: true,
+            ..DisputePolicy::default()
+        };
+        let txs = vec![
+            Transaction {
+                id: 1,
+                client: 1,
+                payload: TxPayload::Deposit { amount: 10.into() },
+                timestamp: None,
+            },
+            Transaction {
+                id: 2,
+                client: 1,
+                payload: TxPayload::Withdrawal { amount: 5.into() },
+                timestamp: None,
+            },
+            Transaction {
+                id: 1,
+                client: 1,
+                payload: TxPayload::Dispute { amount: None },
+                timestamp: None,
+            },
+        ];
+
+        let mut client = Client::default();
+        for tx in txs {
+            client
+                .process_transaction(tx, &Limits::default(), &policy, Timestamp::UNIX_EPOCH)
+                .unwrap();
+        }
+
+        assert_eq!(client.account.available_funds(), (-5).into());
+        assert_eq!(client.account.held_funds(), 10.into());
+        assert_eq!(client.account.deficit(), 5.into());
+        assert!(client.disputes.is_disputed(1));
+
+        let chargeback = Transaction {
+            id: 1,
+            client: 1,
+            payload: TxPayload::Chargeback { amount: None },
+            timestamp: None,
+        };
+        client
+            .process_transaction(
+                chargeback,
+                &Limits::default(),
+                &policy,
+                Timestamp::UNIX_EPOCH,
+            )
+            .unwrap();
+
+        // The client owes the withdrawn funds.
+        assert_eq!(client.account.total_funds(), (-5).into());
+        assert_eq!(client.account.deficit(), 5.into());
+        assert!(client.account.is_locked());
+    }
+
     #[test]
     fn test_dont_process_tx_after_locked() {
         let txs = vec![
             Transaction {
                 id: 1,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Deposit { amount: 10.into() },
             },
             Transaction {
                 id: 1,
                 client: 1,
-                payload: TxPayload::Dispute,
+                timestamp: None,
+                payload: TxPayload::Dispute { amount: None },
             },
             Transaction {
                 id: 1,
                 client: 1,
-                payload: TxPayload::Chargeback,
+                timestamp: None,
+                payload: TxPayload::Chargeback { amount: None },
             },
             Transaction {
                 id: 4,
                 client: 1,
+                timestamp: None,
                 payload: TxPayload::Deposit { amount: 10.into() },
             },
         ];
 
         let mut client = Client::default();
         for tx in &txs {
-            client.process_transaction(*tx);
+            let _ = client.process(*tx);
+        }
+
+        assert_eq!(client.account.total_funds(), 0.into());
+        assert!(client.account.is_locked());
+        assert!(!client.txs.contains_key(&4));
+    }
+
+    #[test]
+    fn test_rejection_reasons() {
+        let mut client = Client::default();
+
+        let deposit = Transaction {
+            id: 1,
+            client: 1,
+            timestamp: None,
+            payload: TxPayload::Deposit { amount: 10.into() },
+        };
+        let withdrawal = Transaction {
+            id: 2,
+            client: 1,
+            timestamp: None,
+            payload: TxPayload::Withdrawal { amount: 5.into() },
+        };
+        let dispute = |id| Transaction {
+            id,
+            client: 1,
+            timestamp: None,
+            payload: TxPayload::Dispute { amount: None },
+        };
+        let resolve = Transaction {
+            id: 1,
+            client: 1,
+            timestamp: None,
+            payload: TxPayload::Resolve { amount: None },
+        };
+
+        assert_eq!(client.process(deposit), Ok(()));
+        assert_eq!(
+            client.process(deposit),
+            Err(Rejection::DuplicateTransaction)
+        );
+        for payload in [
+            TxPayload::Deposit { amount: 11.into() },
+            TxPayload::Withdrawal { amount: 10.into() },
+        ] {
+            let conflicting = Transaction { payload, ..deposit };
+            assert_eq!(
+                client.process(conflicting),
+                Err(Rejection::ConflictingTransaction)
+            );
+        }
+        assert_eq!(client.process(resolve), Err(Rejection::NotDisputed));
+        assert_eq!(
+            client.process(dispute(3)),
+            Err(Rejection::UnknownTransaction)
+        );
+        assert_eq!(client.process(withdrawal), Ok(()));
+        assert_eq!(client.process(dispute(2)), Err(Rejection::NotDisputable));
+        assert_eq!(
+            client.process(dispute(1)),
+            Err(Rejection::InsufficientFunds)
+        );
+    }
+
+    #[test]
+    fn test_non_positive_amounts() {
+        let mut client = Client::default();
+        let tx = |id, payload| Transaction {
+            id,
+            client: 1,
+            timestamp: None,
+            payload,
+        };
+        client
+            .process(tx(1, TxPayload::Deposit { amount: 5.into() }))
+            .unwrap();
+
+        for (id, amount) in [(2, Amount::ZERO), (3, Amount::from(-10))] {
+            for payload in [
+                TxPayload::Deposit { amount },
+                TxPayload::Withdrawal { amount },
+            ] {
+                assert_eq!(
+                    client.process(tx(id, payload)),
+                    Err(Rejection::InvalidAmount)
+                );
+            }
+        }
+        assert_eq!(client.account.available_funds(), 5.into());
+        assert!(client.txs.keys().eq([&1]));
+        assert_eq!(client.history.len(), 5);
+    }
+
+    #[test]
+    fn test_out_of_order_timestamps() {
+        let deposit = |id, timestamp| Transaction {
+            id,
+            client: 1,
+            payload: TxPayload::Deposit { amount: 1.into() },
+            timestamp,
+        };
+        let limits = Limits::default();
+        let mut client = Client::default();
+
+        let t1 = Timestamp::from_millis(1_000);
+        let t2 = Timestamp::from_millis(2_000);
+        assert_eq!(
+            client.process_transaction(
+                deposit(1, Some(t2)),
+                &limits,
+                &DisputePolicy::default(),
+                t2
+            ),
+            Ok(())
+        );
+        // Older transactions are still applied, only reported as out of order.
+        assert!(client.is_out_of_order(&deposit(2, Some(t1))));
+        assert_eq!(
+            client.process_transaction(
+                deposit(2, Some(t1)),
+                &limits,
+                &DisputePolicy::default(),
+                t1
+            ),
+            Ok(())
+        );
+        assert!(client.is_out_of_order(&deposit(4, Some(t1))));
+        assert!(!client.is_out_of_order(&deposit(4, Some(t2))));
+        // Same timestamp is fine.
+        assert_eq!(
+            client.process_transaction(
+                deposit(3, Some(t2)),
+                &limits,
+                &DisputePolicy::default(),
+                t2
+            ),
+            Ok(())
+        );
+        // The engine's clock is not checked.
+        assert_eq!(
+            client.process_transaction(deposit(4, None), &limits, &DisputePolicy::default(), t1),
+            Ok(())
+        );
+
+        assert_eq!(client.account.available_funds(), 4.into());
+        assert_eq!(client.txs[&1].timestamp, Some(t2));
+        assert_eq!(client.txs[&4].timestamp, Some(t1));
+    }
+
+    #[test]
+    fn test_dispute_window() {
+        let policy = DisputePolicy {
+            window: Some(Duration::from_secs(60)),
+            ..DisputePolicy::default()
+        };
+        let limits = Limits::default();
+        let mut client = Client::default();
+
+        let tx = |id, payload| Transaction {
+            id,
+            client: 1,
+            payload,
+            timestamp: None,
+        };
+        let deposited_at = Timestamp::from_millis(0);
+        let deposit = |id| tx(id, TxPayload::Deposit { amount: 1.into() });
+        client
+            .process_transaction(deposit(1), &limits, &policy, deposited_at)
+            .unwrap();
+        client
+            .process_transaction(deposit(2), &limits, &policy, deposited_at)
+            .unwrap();
+
+        let last_moment = Timestamp::from_millis(60_000);
+        assert_eq!(
+            client.process_transaction(
+                tx(1, TxPayload::Dispute { amount: None }),
+                &limits,
+                &policy,
+                last_moment
+            ),
+            Ok(())
+        );
+
+        let too_late = Timestamp::from_millis(60_001);
+        assert_eq!(
+            client.process_transaction(
+                tx(2, TxPayload::Dispute { amount: None }),
+                &limits,
+                &policy,
+                too_late
+            ),
+            Err(Rejection::DisputeWindowExpired)
+        );
+        assert_eq!(client.account.held_funds(), 1.into());
+    }
+
+    #[test]
+    fn test_expire_dispute() {
+        let mut client = Client::default();
+        let tx = |id, payload| Transaction {
+            id,
+            client: 1,
+            payload,
+            timestamp: None,
+        };
+
+        for id in [1, 2] {
+            client
+                .process(tx(id, TxPayload::Deposit { amount: 10.into() }))
+                .unwrap();
+            client
+                .process(tx(id, TxPayload::Dispute { amount: None }))
+                .unwrap();
+        }
+
+        let opened_at = Timestamp::UNIX_EPOCH;
+        let resolve = DisputePolicy::default();
+        let chargeback = DisputePolicy {
+            on_expiry: ExpiryAction::Chargeback,
+            ..DisputePolicy::default()
+        };
+        assert!(
+            client
+                .expire_dispute(1, Timestamp::from_millis(1), &resolve, opened_at)
+                .is_none()
+        );
+        assert!(
+            client
+                .expire_dispute(1, opened_at, &resolve, opened_at)
+                .is_some()
+        );
+        assert!(
+            client
+                .expire_dispute(1, opened_at, &resolve, opened_at)
+                .is_none()
+        );
+        assert_eq!(client.account.available_funds(), 10.into());
+        assert_eq!(client.account.held_funds(), 10.into());
+
+        assert!(
+            client
+                .expire_dispute(2, opened_at, &chargeback, opened_at)
+                .is_some()
+        );
+        assert_eq!(client.account.total_funds(), 10.into());
+        assert!(client.account.is_locked());
+
+        // Expirations have no history entry, but are part of the postings.
+        let kinds: Vec<_> = client
+            .postings()
+            .iter()
+            .map(|posting| (posting.tx, posting.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (1, PostingKind::Deposit),
+                (1, PostingKind::Hold),
+                (2, PostingKind::Deposit),
+                (2, PostingKind::Hold),
+                (1, PostingKind::Release),
+                (2, PostingKind::Chargeback),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partial_disputes() {
+        let mut client = Client::default();
+        let tx = |payload| Transaction {
+            id: 1,
+            client: 1,
+            payload,
+            timestamp: None,
+        };
+        let dispute = |amount: Option<u32>| {
+            tx(TxPayload::Dispute {
+                amount: amount.map(Amount::from),
+            })
+        };
+
+        client
+            .process(tx(TxPayload::Deposit { amount: 10.into() }))
+            .unwrap();
+
+        assert_eq!(client.process(dispute(Some(4))), Ok(()));
+        assert_eq!(client.process(dispute(Some(4))), Ok(()));
+        assert_eq!(client.account.held_funds(), 8.into());
+        assert_eq!(
+            client.process(dispute(Some(3))),
+            Err(Rejection::InvalidAmount)
+        );
+        assert_eq!(
+            client.process(dispute(Some(0))),
+            Err(Rejection::InvalidAmount)
+        );
+        // Without an amount, disputes everything left.
+        assert_eq!(client.process(dispute(None)), Ok(()));
+        assert_eq!(client.account.held_funds(), 10.into());
+        assert_eq!(
+            client.process(dispute(None)),
+            Err(Rejection::AlreadyDisputed)
+        );
+
+        let resolve = tx(TxPayload::Resolve {
+            amount: Some(5.into()),
+        });
+        assert_eq!(client.process(resolve), Ok(()));
+        assert_eq!(client.account.available_funds(), 5.into());
+        assert_eq!(client.process(dispute(Some(5))), Ok(()));
+        assert_eq!(
+            client.process(tx(TxPayload::Resolve {
+                amount: Some(11.into())
+            })),
+            Err(Rejection::InvalidAmount)
+        );
+
+        let chargeback = tx(TxPayload::Chargeback {
+            amount: Some(3.into()),
+        });
+        assert_eq!(client.process(chargeback), Ok(()));
+        assert!(client.account.is_locked());
+        assert_eq!(client.account.held_funds(), 7.into());
+        assert_eq!(client.account.total_funds(), 7.into());
+        let dispute = client.disputes.get(1).unwrap();
+        assert_eq!(dispute.held, 7.into());
+        assert_eq!(dispute.disputed_amount(), 10.into());
+    }
+
+    #[test]
+    fn test_resolving_all_held_closes_dispute() {
+        let mut client = Client::default();
+        let tx = |payload| Transaction {
+            id: 1,
+            client: 1,
+            payload,
+            timestamp: None,
+        };
+
+        client
+            .process(tx(TxPayload::Deposit { amount: 10.into() }))
+            .unwrap();
+        client
+            .process(tx(TxPayload::Dispute {
+                amount: Some(6.into()),
+            }))
+            .unwrap();
+        for _ in 0..2 {
+            client
+                .process(tx(TxPayload::Resolve {
+                    amount: Some(3.into()),
+                }))
+                .unwrap();
         }
 
+        assert!(!client.disputes.is_disputed(1));
+        assert_eq!(
+            client.process(tx(TxPayload::Resolve { amount: None })),
+            Err(Rejection::NotDisputed)
+        );
+        assert_eq!(client.account.available_funds(), 10.into());
+    }
+
+    fn process_with(
+        client: &mut Client,
+        policy: &DisputePolicy,
+        payload: TxPayload,
+    ) -> Result<(), Rejection> {
+        let tx = Transaction {
+            id: 1,
+            client: 1,
+            payload,
+            timestamp: None,
+        };
+        client.process_transaction(tx, &Limits::default(), policy, Timestamp::UNIX_EPOCH)
+    }
+
+    #[test]
+    fn test_dispute_lifecycle() {
+        let policy = DisputePolicy::default();
+        let mut client = Client::default();
+        let mut process = |payload| process_with(&mut client, &policy, payload);
+
+        process(TxPayload::Deposit { amount: 10.into() }).unwrap();
+        process(TxPayload::Dispute { amount: None }).unwrap();
+        process(TxPayload::Resolve { amount: None }).unwrap();
+        // Pre-arbitration.
+        process(TxPayload::Dispute { amount: None }).unwrap();
+        assert_eq!(
+            process(TxPayload::Dispute { amount: None }),
+            Err(Rejection::AlreadyDisputed)
+        );
+        process(TxPayload::Chargeback { amount: None }).unwrap();
+
+        assert_eq!(client.disputes.state(1), Some(DisputeState::Arbitrated));
         assert_eq!(client.account.total_funds(), 0.into());
         assert!(client.account.is_locked());
+    }
+
+    #[test]
+    fn test_arbitrated_disputes_are_final() {
+        let policy = DisputePolicy::default();
+        let mut client = Client::default();
+        let mut process = |payload| process_with(&mut client, &policy, payload);
+
+        process(TxPayload::Deposit { amount: 10.into() }).unwrap();
+        for _ in 0..2 {
+            process(TxPayload::Dispute { amount: None }).unwrap();
+            process(TxPayload::Resolve { amount: None }).unwrap();
+        }
+
+        assert_eq!(
+            process(TxPayload::Dispute { amount: None }),
+            Err(Rejection::InvalidDisputeTransition)
+        );
+        assert_eq!(
+            process(TxPayload::Resolve { amount: None }),
+            Err(Rejection::NotDisputed)
+        );
+        assert_eq!(client.disputes.state(1), Some(DisputeState::Arbitrated));
+        assert_eq!(client.account.available_funds(), 10.into());
+    }
+
+    #[test]
+    fn test_forbidden_dispute_transitions() {
+        let policy = DisputePolicy {
+            transitions: DisputeTransitions::default()
+                .without(DisputeTransition::Reopen)
+                .without(DisputeTransition::Chargeback),
+            ..DisputePolicy::default()
+        };
+        let mut client = Client::default();
+        let mut process = |payload| process_with(&mut client, &policy, payload);
+
+        process(TxPayload::Deposit { amount: 10.into() }).unwrap();
+        process(TxPayload::Dispute { amount: None }).unwrap();
+        assert_eq!(
+            process(TxPayload::Chargeback { amount: None }),
+            Err(Rejection::InvalidDisputeTransition)
+        );
+        process(TxPayload::Resolve { amount: None }).unwrap();
+        assert_eq!(
+            process(TxPayload::Dispute { amount: None }),
+            Err(Rejection::InvalidDisputeTransition)
+        );
+
+        assert_eq!(client.disputes.state(1), Some(DisputeState::Resolved));
+        assert_eq!(client.account.available_funds(), 10.into());
+    }
+
+    #[test]
+    fn test_representment() {
+        let policy = DisputePolicy {
+            transitions: DisputeTransitions::ALL,
+            ..DisputePolicy::default()
+        };
+        let mut client = Client::default();
+        let mut process = |payload| process_with(&mut client, &policy, payload);
+
+        process(TxPayload::Deposit { amount: 10.into() }).unwrap();
+        process(TxPayload::Dispute { amount: None }).unwrap();
+        process(TxPayload::Chargeback { amount: None }).unwrap();
+        process(TxPayload::Dispute { amount: None }).unwrap();
+        // Only the representment itself goes through on the locked account.
+        assert_eq!(
+            process(TxPayload::Deposit { amount: 1.into() }),
+            Err(Rejection::AccountLocked)
+        );
+        process(TxPayload::Resolve { amount: None }).unwrap();
+
+        assert_eq!(client.disputes.state(1), Some(DisputeState::Arbitrated));
+        assert_eq!(client.account.available_funds(), 10.into());
+        assert_eq!(client.account.held_funds(), 0.into());
+        assert!(client.account.is_locked());
+    }
+
+    #[test]
+    fn test_no_representment_by_default() {
+        let policy = DisputePolicy::default();
+        let mut client = Client::default();
+        let mut process = |payload| process_with(&mut client, &policy, payload);
+
+        process(TxPayload::Deposit { amount: 10.into() }).unwrap();
+        process(TxPayload::Dispute { amount: None }).unwrap();
+        process(TxPayload::Chargeback { amount: None }).unwrap();
+        assert_eq!(
+            process(TxPayload::Dispute { amount: None }),
+            Err(Rejection::AccountLocked)
+        );
+        assert_eq!(client.disputes.state(1), Some(DisputeState::ChargedBack));
+    }
+
+    #[test]
+    fn test_withdrawal_limits() {
+        let limits = Limits {
+            max_withdrawal: Some(50.into()),
+            max_daily_withdrawal: Some(80.into()),
+            ..Limits::default()
+        };
+
+        let mut client = Client::default();
+        let now = Timestamp::UNIX_EPOCH;
+        let withdraw = |id, amount: u32| Transaction {
+            id,
+            client: 1,
+            timestamp: None,
+            payload: TxPayload::Withdrawal {
+                amount: amount.into(),
+            },
+        };
+
+        let deposit = Transaction {
+            id: 1,
+            client: 1,
+            timestamp: None,
+            payload: TxPayload::Deposit { amount: 200.into() },
+        };
+        assert_eq!(
+            client.process_transaction(deposit, &limits, &DisputePolicy::default(), now),
+            Ok(())
+        );
+
+        assert_eq!(
+            client.process_transaction(withdraw(2, 60), &limits, &DisputePolicy::default(), now),
+            Err(Rejection::LimitExceeded(Limit::Withdrawal))
+        );
+        assert_eq!(
+            client.process_transaction(withdraw(3, 50), &limits, &DisputePolicy::default(), now),
+            Ok(())
+        );
+        assert_eq!(
+            client.process_transaction(withdraw(4, 40), &limits, &DisputePolicy::default(), now),
+            Err(Rejection::LimitExceeded(Limit::DailyWithdrawal))
+        );
+        assert_eq!(
+            client.process_transaction(withdraw(5, 30), &limits, &DisputePolicy::default(), now),
+            Ok(())
+        );
+
+        assert_eq!(client.account.available_funds(), 120.into());
+        assert!(!client.txs.contains_key(&2));
         assert!(!client.txs.contains_key(&4));
     }
+
+    #[test]
+    fn test_velocity_limit() {
+        let limits = Limits {
+            max_transactions: Some(RateLimit {
+                count: 2,
+                window: Duration::from_secs(1),
+            }),
+            ..Limits::default()
+        };
+
+        let mut client = Client::default();
+        let deposit = |id| Transaction {
+            id,
+            client: 1,
+            timestamp: None,
+            payload: TxPayload::Deposit { amount: 1.into() },
+        };
+
+        let now = Timestamp::UNIX_EPOCH;
+        assert_eq!(
+            client.process_transaction(deposit(1), &limits, &DisputePolicy::default(), now),
+            Ok(())
+        );
+        assert_eq!(
+            client.process_transaction(deposit(2), &limits, &DisputePolicy::default(), now),
+            Ok(())
+        );
+        assert_eq!(
+            client.process_transaction(deposit(3), &limits, &DisputePolicy::default(), now),
+            Err(Rejection::LimitExceeded(Limit::Velocity))
+        );
+
+        let later = Timestamp::from_millis(1_000);
+        assert_eq!(
+            client.process_transaction(deposit(3), &limits, &DisputePolicy::default(), later),
+            Ok(())
+        );
+        assert_eq!(client.account.available_funds(), 3.into());
+    }
+
+    #[test]
+    fn test_held_limit() {
+        let limits = Limits {
+            max_held: Some(15.into()),
+            ..Limits::default()
+        };
+
+        let mut client = Client::default();
+        let now = Timestamp::UNIX_EPOCH;
+        for (id, amount) in [(1, 10), (2, 10)] {
+            let deposit = Transaction {
+                id,
+                client: 1,
+                timestamp: None,
+                payload: TxPayload::Deposit {
+                    amount: amount.into(),
+                },
+            };
+            client
+                .process_transaction(deposit, &limits, &DisputePolicy::default(), now)
+                .unwrap();
+        }
+
+        let dispute = |id| Transaction {
+            id,
+            client: 1,
+            timestamp: None,
+            payload: TxPayload::Dispute { amount: None },
+        };
+        assert_eq!(
+            client.process_transaction(dispute(1), &limits, &DisputePolicy::default(), now),
+            Ok(())
+        );
+        assert_eq!(
+            client.process_transaction(dispute(2), &limits, &DisputePolicy::default(), now),
+            Err(Rejection::LimitExceeded(Limit::Held))
+        );
+        assert_eq!(client.account.held_funds(), 10.into());
+    }
 }
 
 #[cfg(test)]
 mod proptests {
+    use std::collections::HashSet;
+
     use super::*;
     use crate::{
-        Amount,
+        Amount, Statement,
         transaction::{any_transaction, any_transaction_with_types},
     };
 
@@ -353,11 +1499,12 @@ mod proptests {
             let mut client = Client::default();
 
             for &tx in &txs {
-                client.process_transaction(tx);
+                let _ = client.process(tx);
             }
 
+            // Deposits of a zero amount, e.g. rounded to zero at scale 4, are rejected.
             for tx in &txs {
-                if let TxPayload::Deposit {..} = tx.payload {
+                if matches!(tx.payload, TxPayload::Deposit { amount } if amount > Amount::ZERO) {
                     prop_assert!(client.txs.contains_key(&tx.id));
                 }
             }
@@ -384,31 +1531,22 @@ mod proptests {
         fn test_client_process_transaction_with_disputes(txs in any_ledger(10_000)) {
             let mut client = Client::default();
 
-            for &tx in &txs {
-                client.process_transaction(tx);
+            let results: Vec<_> = txs.iter().map(|&tx| client.process(tx)).collect();
+
+            // Deposits coming after a chargeback locked the account are refused, every
+            // other deposit is kept, whether the account ends up locked or not.
+            for (tx, &result) in txs.iter().zip(&results) {
+                if matches!(tx.payload, TxPayload::Deposit { amount } if amount > Amount::ZERO)
+                    && result != Err(Rejection::AccountLocked)
+                {
+                    prop_assert!(client.txs.contains_key(&tx.id));
+                }
             }
 
             let mut expected_total = Amount::ZERO;
-            for (idx, &tx) in txs.iter().enumerate() {
-                match tx.payload {
-                    TxPayload::Deposit {..} => {
-                        prop_assert!(client.txs.contains_key(&tx.id));
-                    },
-                    TxPayload::Chargeback => {
-                        if let Some(original_tx) = client.txs.get(&tx.id) {
-                            let og_idx = txs.iter().position(|&t| t.id == tx.id).unwrap();
-                            if og_idx >= idx {
-                                continue;
-                            }
-
-                            if let TxPayload::Deposit { amount } = original_tx.payload {
-                                expected_total -= amount;
-                            }
-                        }
-                    }
-                    _ => continue,
-                }
-                if let TxPayload::Deposit {..} = tx.payload {
+            for (id, dispute) in client.disputes.iter() {
+                if !dispute.charged_back.is_zero() {
+                    expected_total -= client.txs[&id].deposited_amount().unwrap();
                 }
             }
 
@@ -421,11 +1559,9 @@ mod proptests {
                 }
             }
 
-            for &disputed_tx in &client.disputes.txs {
-                if let Some(tx) = client.txs.get(&disputed_tx) {
-                    if let TxPayload::Deposit { amount } = tx.payload {
-                        expected_held += amount;
-                    }
+            for (id, dispute) in client.disputes.iter() {
+                if dispute.state.is_open() {
+                    expected_held += client.txs[&id].deposited_amount().unwrap();
                 }
             }
 
@@ -435,12 +1571,73 @@ mod proptests {
             prop_assert!(client.account.available_funds() >= Amount::ZERO);
             prop_assert_eq!(client.account.total_funds(), client.account.available_funds() + client.account.held_funds());
 
-            if client.disputes.txs.is_empty() {
-                prop_assert!(!client.account.is_locked(), "account is locked with no chargeback");
+            prop_assert_eq!(
+                client.account.is_locked(),
+                client.disputes.iter().any(|(_, dispute)| !dispute.charged_back.is_zero()),
+                "account lock doesn't match chargebacks"
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_client_partial_disputes(txs in any_partial_ledger(500)) {
+            let mut client = Client::default();
+
+            for &tx in &txs {
+                let _ = client.process(tx);
+
+                prop_assert!(client.account.held_funds() >= Amount::ZERO);
+                prop_assert!(client.account.available_funds() >= Amount::ZERO);
+                prop_assert_eq!(client.account.total_funds(), client.account.available_funds() + client.account.held_funds());
+
+                let mut expected_held = Amount::ZERO;
+                for (id, dispute) in client.disputes.iter() {
+                    prop_assert!(dispute.held >= Amount::ZERO);
+                    prop_assert_eq!(
+                        dispute.state.is_open(),
+                        dispute.held > Amount::ZERO,
+                        "dispute state doesn't match held funds"
+                    );
+
+                    let deposited = client.txs[&id].deposited_amount().unwrap();
+                    prop_assert!(dispute.disputed_amount() <= deposited);
+
+                    expected_held += dispute.held;
+                }
+                prop_assert_eq!(client.account.held_funds(), expected_held);
             }
+
+            let statement = Statement::new(0, Timestamp::UNIX_EPOCH, Timestamp::from_millis(1), &client.postings());
+            prop_assert_eq!(statement.closing.available, client.account.available_funds());
+            prop_assert_eq!(statement.closing.held, client.account.held_funds());
         }
     }
 
+    /// Transactions of a single client over a handful of IDs, with small amounts so
+    /// that partial disputes often hit the same deposit.
+    fn any_partial_ledger(max_size: usize) -> impl Strategy<Value = Vec<Transaction>> {
+        let amount = || (1..=100u32).prop_map(Amount::from);
+        let partial = || prop::option::of(amount());
+
+        let payload = prop_oneof![
+            4 => amount().prop_map(|amount| TxPayload::Deposit { amount }),
+            2 => amount().prop_map(|amount| TxPayload::Withdrawal { amount }),
+            4 => partial().prop_map(|amount| TxPayload::Dispute { amount }),
+            3 => partial().prop_map(|amount| TxPayload::Resolve { amount }),
+            1 => partial().prop_map(|amount| TxPayload::Chargeback { amount }),
+        ];
+
+        let tx = (0..8u32, payload).prop_map(|(id, payload)| Transaction {
+            id,
+            client: 0,
+            payload,
+            timestamp: None,
+        });
+
+        prop::collection::vec(tx, 0..max_size)
+    }
+
     fn any_ledger(max_size: usize) -> impl Strategy<Value = Vec<Transaction>> {
         prop::collection::vec(any_transaction(), 0..max_size).prop_perturb(|mut txs, mut rng| {
             let mut seen_deposits = HashSet::new();
@@ -451,33 +1648,31 @@ mod proptests {
                     TxPayload::Deposit { .. } => {
                         seen_deposits.insert(tx.id);
                     }
-                    TxPayload::Dispute => {
+                    TxPayload::Dispute { .. }
                         if !disputed.is_empty()
                             && !seen_deposits.contains(&tx.id)
-                            && rng.random_bool(0.95)
-                        {
-                            let deposit_id = seen_deposits
-                                .iter()
-                                .nth(rng.random_range(0..seen_deposits.len()))
-                                .copied()
-                                .unwrap();
-                            tx.id = deposit_id;
-                            disputed.insert(deposit_id);
-                        }
+                            && rng.random_bool(0.95) =>
+                    {
+                        let deposit_id = seen_deposits
+                            .iter()
+                            .nth(rng.random_range(0..seen_deposits.len()))
+                            .copied()
+                            .unwrap();
+                        tx.id = deposit_id;
+                        disputed.insert(deposit_id);
                     }
-                    TxPayload::Resolve | TxPayload::Chargeback => {
+                    TxPayload::Resolve { .. } | TxPayload::Chargeback { .. }
                         if !disputed.is_empty()
                             && !disputed.contains(&tx.id)
-                            && rng.random_bool(0.99)
-                        {
-                            let deposit_id = seen_deposits
-                                .iter()
-                                .nth(rng.random_range(0..seen_deposits.len()))
-                                .copied()
-                                .unwrap();
-                            seen_deposits.remove(&deposit_id);
-                            tx.id = deposit_id;
-                        }
+                            && rng.random_bool(0.99) =>
+                    {
+                        let deposit_id = seen_deposits
+                            .iter()
+                            .nth(rng.random_range(0..seen_deposits.len()))
+                            .copied()
+                            .unwrap();
+                        seen_deposits.remove(&deposit_id);
+                        tx.id = deposit_id;
                     }
                     _ => (),
                 }
@@ -0,0 +1,278 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::{Amount, Timestamp, stats::table_size};
+
+/// Rules governing when transactions can be disputed and how long disputes last.
+///
+/// The default policy allows disputing transactions at any time, keeps disputes
+/// open until explicitly resolved or charged back and allows every transition of
+/// the dispute lifecycle except for representment.
+#[derive(Debug, Default, Clone)]
+pub struct DisputePolicy {
+    /// How long after a deposit it can still be disputed.
+    pub window: Option<Duration>,
+    /// How long a dispute can stay open before [`DisputePolicy::on_expiry`] is applied.
+    pub deadline: Option<Duration>,
+    /// What to do with disputes open past their deadline.
+    pub on_expiry: ExpiryAction,
+    /// Which transitions of the dispute lifecycle are allowed.
+    pub transitions: DisputeTransitions,
+    /// Hold disputed funds even if they were already withdrawn, leaving the client
+    /// with negative available funds, i.e. a deficit.
+    ///
+    /// Otherwise, such disputes are rejected.
+    pub allow_deficit: bool,
+}
+
+/// Action automatically taken on disputes open past their deadline.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExpiryAction {
+    /// Release the held funds back to the client.
+    #[default]
+    Resolve,
+    /// Charge back the held funds and lock the account.
+    Chargeback,
+}
+
+/// The state of a disputed transaction.
+///
+/// ```text
+///          resolve                  dispute
+///   Open ──────────────▶ Resolved ────────────┐
+///    │                                        ▼
+///    │ chargeback                          Reopened ──────────────▶ Arbitrated
+///    │                                        ▲       resolve or
+///    └───────────────▶ ChargedBack ───────────┘       chargeback
+///                                   dispute
+///                               (representment)
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DisputeState {
+    /// The dispute is open and holding funds.
+    Open,
+    /// The dispute was resolved, releasing the held funds.
+    Resolved,
+    /// The dispute was charged back, and the client's account locked.
+    ChargedBack,
+    /// A resolved or charged back dispute was disputed again, pending arbitration.
+    Reopened,
+    /// The reopened dispute was ruled on and can't change anymore.
+    Arbitrated,
+}
+
+impl DisputeState {
+    /// Whether the dispute is pending a resolution.
+    pub fn is_open(self) -> bool {
+        matches!(self, Self::Open | Self::Reopened)
+    }
+}
+
+/// A transition of the dispute lifecycle that can be allowed or forbidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DisputeTransition {
+    /// [`DisputeState::Open`] to [`DisputeState::Resolved`].
+    Resolve,
+    /// [`DisputeState::Open`] to [`DisputeState::ChargedBack`].
+    Chargeback,
+    /// [`DisputeState::Resolved`] to [`DisputeState::Reopened`], i.e. pre-arbitration.
+    Reopen,
+    /// [`DisputeState::ChargedBack`] to [`DisputeState::Reopened`], i.e. the client
+    /// contesting the chargeback.
+    ///
+    /// Allowing it lets dispute-related transactions of the charged back transaction
+    /// through even though the account is locked. If arbitration is resolved in the
+    /// client's favour, the charged back funds are credited back, but the account
+    /// stays locked.
+    Represent,
+    /// [`DisputeState::Reopened`] to [`DisputeState::Arbitrated`].
+    Arbitrate,
+}
+
+/// The set of allowed [`DisputeTransition`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisputeTransitions(u8);
+
+impl DisputeTransitions {
+    /// No transitions allowed, disputes stay open forever.
+    pub const NONE: Self = Self(0);
+    /// The whole lifecycle, including representment.
+    pub const ALL: Self = Self::NONE
+        .with(DisputeTransition::Resolve)
+        .with(DisputeTransition::Chargeback)
+        .with(DisputeTransition::Reopen)
+        .with(DisputeTransition::Represent)
+        .with(DisputeTransition::Arbitrate);
+
+    pub const fn with(self, transition: DisputeTransition) -> Self {
+        Self(self.0 | Self::bit(transition))
+    }
+
+    pub const fn without(self, transition: DisputeTransition) -> Self {
+        Self(self.0 & !Self::bit(transition))
+    }
+
+    pub const fn allows(self, transition: DisputeTransition) -> bool {
+        self.0 & Self::bit(transition) != 0
+    }
+
+    const fn bit(transition: DisputeTransition) -> u8 {
+        1 << transition as u8
+    }
+}
+
+impl Default for DisputeTransitions {
+    fn default() -> Self {
+        Self::ALL.without(DisputeTransition::Represent)
+    }
+}
+
+/// A disputed transaction of a client.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Dispute {
+    pub(crate) state: DisputeState,
+    /// When the dispute was last opened or reopened.
+    ///
+    /// Disputing more of a transaction while its dispute is open doesn't change it.
+    pub(crate) opened_at: Timestamp,
+    /// Funds currently held by the dispute.
+    pub(crate) held: Amount,
+    /// Funds charged back so far.
+    pub(crate) charged_back: Amount,
+    /// Whether the dispute was reopened after a chargeback.
+    pub(crate) represented: bool,
+}
+
+impl Dispute {
+    /// The amount of the transaction that is held or was charged back.
+    pub(crate) fn disputed_amount(&self) -> Amount {
+        self.held + self.charged_back
+    }
+
+    /// The transition actually taken by a resolve or chargeback of the dispute, and the
+    /// state it closes the dispute in, if the dispute is open.
+    ///
+    /// Ruling on a reopened dispute is an arbitration, whichever way it goes.
+    pub(crate) fn closing(
+        &self,
+        transition: DisputeTransition,
+    ) -> Option<(DisputeTransition, DisputeState)> {
+        match self.state {
+            DisputeState::Open if transition == DisputeTransition::Resolve => {
+                Some((transition, DisputeState::Resolved))
+            }
+            DisputeState::Open => Some((transition, DisputeState::ChargedBack)),
+            DisputeState::Reopened => {
+                Some((DisputeTransition::Arbitrate, DisputeState::Arbitrated))
+            }
+            _ => None,
+        }
+    }
+
+    /// Stop holding `amount`, moving to `closed` if nothing is left held.
+    pub(crate) fn release(&mut self, amount: Amount, closed: DisputeState) {
+        self.held -= amount;
+        if self.held.is_zero() {
+            self.state = closed;
+        }
+    }
+
+    /// Charge back `amount` of the held funds, moving to `closed` if nothing is left
+    /// held.
+    pub(crate) fn charge_back(&mut self, amount: Amount, closed: DisputeState) {
+        self.charged_back += amount;
+        self.release(amount, closed);
+    }
+
+    /// Credit back `amount` of the funds charged back, after representment.
+    pub(crate) fn reverse_chargeback(&mut self, amount: Amount, closed: DisputeState) {
+        self.charged_back -= amount;
+        self.state = closed;
+    }
+}
+
+/// Tracks disputed transactions for a client.
+///
+/// Transactions can be disputed partially, and partial disputes can be resolved or
+/// charged back in multiple steps. Their state only changes once nothing is held.
+#[derive(Debug, Default)]
+pub(crate) struct Disputes {
+    txs: HashMap<u32, Dispute>,
+}
+
+impl Disputes {
+    pub(crate) fn get(&self, tx: u32) -> Option<&Dispute> {
+        self.txs.get(&tx)
+    }
+
+    pub(crate) fn get_mut(&mut self, tx: u32) -> Option<&mut Dispute> {
+        self.txs.get_mut(&tx)
+    }
+
+    pub(crate) fn heap_size(&self) -> usize {
+        table_size::<(u32, Dispute)>(self.txs.capacity())
+    }
+
+    pub(crate) fn state(&self, tx: u32) -> Option<DisputeState> {
+        self.get(tx).map(|dispute| dispute.state)
+    }
+
+    /// Hold `amount` more of the transaction, opening a dispute if needed or reopening
+    /// a resolved one.
+    pub(crate) fn hold(&mut self, tx: u32, amount: Amount, now: Timestamp) {
+        let dispute = self.txs.entry(tx).or_insert(Dispute {
+            state: DisputeState::Open,
+            opened_at: now,
+            held: Amount::ZERO,
+            charged_back: Amount::ZERO,
+            represented: false,
+        });
+        if dispute.state == DisputeState::Resolved {
+            dispute.state = DisputeState::Reopened;
+            dispute.opened_at = now;
+        }
+        dispute.held += amount;
+    }
+
+    /// Reopen a charged back dispute for the client to contest the chargeback.
+    ///
+    /// The funds are already gone, so there is nothing to hold.
+    pub(crate) fn represent(&mut self, tx: u32, now: Timestamp) {
+        if let Some(dispute) = self.txs.get_mut(&tx) {
+            dispute.state = DisputeState::Reopened;
+            dispute.opened_at = now;
+            dispute.represented = true;
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_disputed(&self, tx: u32) -> bool {
+        matches!(
+            self.state(tx),
+            Some(DisputeState::Open | DisputeState::Reopened | DisputeState::ChargedBack)
+        )
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (u32, &Dispute)> {
+        self.txs.iter().map(|(&tx, dispute)| (tx, dispute))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions_set() {
+        let transitions = DisputeTransitions::default();
+        assert!(transitions.allows(DisputeTransition::Resolve));
+        assert!(transitions.allows(DisputeTransition::Arbitrate));
+        assert!(!transitions.allows(DisputeTransition::Represent));
+
+        let transitions = transitions.without(DisputeTransition::Reopen);
+        assert!(!transitions.allows(DisputeTransition::Reopen));
+        assert!(transitions.allows(DisputeTransition::Chargeback));
+
+        assert!(!DisputeTransitions::NONE.allows(DisputeTransition::Resolve));
+        assert!(DisputeTransitions::ALL.allows(DisputeTransition::Represent));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{Amount, DisputeState, Rejection, Timestamp, Transaction, dispute::Dispute};
+
+/// What happened to a processed transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TxStatus {
+    /// The transaction was applied to the client's account.
+    Accepted,
+    /// The transaction was rejected and left the client's account untouched.
+    Rejected(Rejection),
+    /// The deposit was accepted and later disputed, the dispute being in the given
+    /// state.
+    Disputed(DisputeState),
+}
+
+/// A processed transaction, as stored in a client's history.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HistoryEntry {
+    /// The transaction, always timestamped.
+    pub(crate) tx: Transaction,
+    pub(crate) outcome: Result<(), Rejection>,
+}
+
+/// A processed transaction and its current status.
+#[derive(Debug, Clone, Copy)]
+pub struct TransactionRecord<'a> {
+    pub(crate) tx: &'a Transaction,
+    pub(crate) status: TxStatus,
+}
+
+impl<'a> TransactionRecord<'a> {
+    /// The transaction as processed, timestamped with the engine's clock if it had no
+    /// timestamp.
+    pub fn transaction(&self) -> &'a Transaction {
+        self.tx
+    }
+
+    pub fn status(&self) -> TxStatus {
+        self.status
+    }
+}
+
+/// A disputed deposit and the state of its dispute.
+#[derive(Debug, Clone, Copy)]
+pub struct DisputeRecord<'a> {
+    pub(crate) tx: &'a Transaction,
+    pub(crate) dispute: &'a Dispute,
+}
+
+impl<'a> DisputeRecord<'a> {
+    /// The disputed deposit.
+    pub fn transaction(&self) -> &'a Transaction {
+        self.tx
+    }
+
+    pub fn state(&self) -> DisputeState {
+        self.dispute.state
+    }
+
+    /// When the dispute was last opened or reopened.
+    pub fn opened_at(&self) -> Timestamp {
+        self.dispute.opened_at
+    }
+
+    /// Funds currently held by the dispute.
+    pub fn held(&self) -> Amount {
+        self.dispute.held
+    }
+
+    /// Funds charged back so far.
+    pub fn charged_back(&self) -> Amount {
+        self.dispute.charged_back
+    }
+}
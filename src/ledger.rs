@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{Amount, Balances, Posting, PostingKind, Timestamp};
+
+/// An account of the double-entry ledger.
+///
+/// Client accounts are liabilities of the house, so they are increased by credits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LedgerAccount {
+    /// Funds the client can use.
+    ClientAvailable(u16),
+    /// Client funds held by disputes.
+    ClientHeld(u16),
+    /// Funds actually held by the house.
+    HouseCash,
+    /// Fees charged to clients. The engine doesn't charge fees yet.
+    FeeIncome,
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ClientAvailable(client) => write!(f, "client:{client}:available"),
+            Self::ClientHeld(client) => write!(f, "client:{client}:held"),
+            Self::HouseCash => f.write_str("house:cash"),
+            Self::FeeIncome => f.write_str("house:fee_income"),
+        }
+    }
+}
+
+/// A balanced journal entry, debiting one account and crediting another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub timestamp: Timestamp,
+    /// The deposit or withdrawal the entry is about.
+    pub tx: u32,
+    pub kind: PostingKind,
+    pub debit: LedgerAccount,
+    pub credit: LedgerAccount,
+    pub amount: Amount,
+}
+
+impl JournalEntry {
+    /// The journal entry recording a client's posting.
+    pub(crate) fn new(client: u16, posting: &Posting) -> Self {
+        use LedgerAccount::*;
+
+        let (debit, credit) = match posting.kind {
+            PostingKind::Deposit => (HouseCash, ClientAvailable(client)),
+            PostingKind::Withdrawal => (ClientAvailable(client), HouseCash),
+            PostingKind::Hold => (ClientAvailable(client), ClientHeld(client)),
+            PostingKind::Release => (ClientHeld(client), ClientAvailable(client)),
+            PostingKind::Chargeback => (ClientHeld(client), HouseCash),
+            // The house recovers the funds it paid out on the chargeback.
+            PostingKind::ChargebackReversal => (HouseCash, ClientAvailable(client)),
+        };
+
+        Self {
+            timestamp: posting.timestamp,
+            tx: posting.tx,
+            kind: posting.kind,
+            debit,
+            credit,
+            amount: posting.amount,
+        }
+    }
+}
+
+/// The debit and credit totals of every ledger account.
+///
+/// The ledger is derived from the clients' postings, each posted as a single entry
+/// debiting and crediting the same amount, so its trial balance is always balanced.
+/// What it can be checked for is that each client's balances, as derived from its
+/// postings, match its [`crate::Account`], see [`crate::Engine::verify`].
+#[derive(Debug, Default, Clone)]
+pub struct Ledger {
+    accounts: BTreeMap<LedgerAccount, TrialBalanceRow>,
+}
+
+impl Ledger {
+    pub(crate) fn post(&mut self, entry: &JournalEntry) {
+        self.row(entry.debit).debits += entry.amount;
+        self.row(entry.credit).credits += entry.amount;
+    }
+
+    fn row(&mut self, account: LedgerAccount) -> &mut TrialBalanceRow {
+        self.accounts.entry(account).or_insert(TrialBalanceRow {
+            account,
+            debits: Amount::ZERO,
+            credits: Amount::ZERO,
+        })
+    }
+
+    /// The client's balances according to the ledger.
+    pub fn client_balances(&self, client: u16) -> Balances {
+        let credit_balance = |account| {
+            self.accounts
+                .get(&account)
+                .map_or(Amount::ZERO, |row| -row.balance())
+        };
+
+        Balances {
+            available: credit_balance(LedgerAccount::ClientAvailable(client)),
+            held: credit_balance(LedgerAccount::ClientHeld(client)),
+        }
+    }
+
+    /// The totals of every account with postings, clients first.
+    pub fn trial_balance(&self) -> TrialBalance {
+        TrialBalance {
+            rows: self.accounts.values().copied().collect(),
+        }
+    }
+}
+
+/// The debit and credit totals of a ledger account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrialBalanceRow {
+    pub account: LedgerAccount,
+    pub debits: Amount,
+    pub credits: Amount,
+}
+
+impl TrialBalanceRow {
+    /// The debit balance of the account, negative for credit balances.
+    pub fn balance(&self) -> Amount {
+        self.debits - self.credits
+    }
+}
+
+/// The totals of every ledger account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrialBalance {
+    pub rows: Vec<TrialBalanceRow>,
+}
+
+impl TrialBalance {
+    pub fn total_debits(&self) -> Amount {
+        self.rows.iter().map(|row| row.debits).sum()
+    }
+
+    pub fn total_credits(&self) -> Amount {
+        self.rows.iter().map(|row| row.credits).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trial_balance() {
+        let posting = |tx, kind, amount: i32| Posting {
+            timestamp: Timestamp::UNIX_EPOCH,
+            tx,
+            kind,
+            amount: Amount::from(amount),
+        };
+        let postings = [
+            posting(1, PostingKind::Deposit, 100),
+            posting(2, PostingKind::Withdrawal, 30),
+            posting(1, PostingKind::Hold, 50),
+            posting(1, PostingKind::Chargeback, 20),
+        ];
+
+        let mut ledger = Ledger::default();
+        for posting in &postings {
+            ledger.post(&JournalEntry::new(7, posting));
+        }
+
+        let balances = ledger.client_balances(7);
+        assert_eq!(balances.available, Amount::from(20));
+        assert_eq!(balances.held, Amount::from(30));
+
+        let trial_balance = ledger.trial_balance();
+        assert_eq!(trial_balance.total_debits(), Amount::from(200));
+        assert_eq!(trial_balance.total_credits(), Amount::from(200));
+
+        let cash = trial_balance
+            .rows
+            .iter()
+            .find(|row| row.account == LedgerAccount::HouseCash)
+            .unwrap();
+        assert_eq!(cash.balance(), Amount::from(50));
+    }
+
+    #[test]
+    fn test_chargeback_reversal_entry() {
+        let posting = |kind| Posting {
+            timestamp: Timestamp::UNIX_EPOCH,
+            tx: 1,
+            kind,
+            amount: Amount::from(20),
+        };
+
+        let chargeback = JournalEntry::new(7, &posting(PostingKind::Chargeback));
+        assert_eq!(chargeback.debit, LedgerAccount::ClientHeld(7));
+        assert_eq!(chargeback.credit, LedgerAccount::HouseCash);
+
+        let reversal = JournalEntry::new(7, &posting(PostingKind::ChargebackReversal));
+        assert_eq!(reversal.debit, LedgerAccount::HouseCash);
+        assert_eq!(reversal.credit, LedgerAccount::ClientAvailable(7));
+    }
+}
@@ -1,15 +1,71 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, hash_map::Entry},
+    time::Instant,
+};
+
 use bit_set::BitSet;
 
-use crate::{account::Account, client::Client};
+use crate::{client::Client, replay::ReplayGuard, transaction::TxPayload};
 
 mod account;
 mod client;
+mod dispute;
+mod history;
+mod ledger;
+mod limits;
+#[cfg(test)]
+mod model;
+mod rejection;
+mod replay;
+mod report;
+mod schema;
+mod statement;
+mod stats;
+mod time;
 mod transaction;
+mod verify;
+mod workload;
 
 type Amount = fastnum::D256;
 
+/// Number of possible client IDs.
+const CLIENTS: usize = u16::MAX as usize + 1;
+
+#[doc(inline)]
+pub use self::account::{Account, AccountError};
+#[doc(inline)]
+pub use self::dispute::{
+    DisputePolicy, DisputeState, DisputeTransition, DisputeTransitions, ExpiryAction,
+};
+#[doc(inline)]
+pub use self::history::{DisputeRecord, TransactionRecord, TxStatus};
+#[doc(inline)]
+pub use self::ledger::{JournalEntry, Ledger, LedgerAccount, TrialBalance, TrialBalanceRow};
+#[doc(inline)]
+pub use self::limits::{ConfigError, Limits, LimitsConfig, RateLimit};
+#[doc(inline)]
+pub use self::rejection::{Limit, Rejection, Severity};
+#[doc(inline)]
+pub use self::report::{
+    AccountFilter, AccountReport, Column, ParseReportError, ReportRow, SortKey,
+};
+#[doc(inline)]
+pub use self::schema::{ColumnRef, Field, Schema, SchemaError, SchemaMapping};
+#[doc(inline)]
+pub use self::statement::{
+    Balances, DisputeSummary, Posting, PostingKind, Statement, StatementLine,
+};
+#[doc(inline)]
+pub use self::stats::{EngineStats, Histogram};
+#[doc(inline)]
+pub use self::time::{Clock, ParseTimestampError, SystemClock, Timestamp};
+#[doc(inline)]
+pub use self::transaction::{Transaction, TransactionKind, UnknownTransactionKind};
 #[doc(inline)]
-pub use self::transaction::Transaction;
+pub use self::verify::{Discrepancy, Reconciliation};
+#[doc(inline)]
+pub use self::workload::{TransactionMix, Workload};
 
 /// Main payment engine structure.
 ///
@@ -19,6 +75,37 @@ pub struct Engine {
     //       and avoid the overhead of a HashMap.
     clients: Vec<Client>,
     seem_clients: BitSet<u64>,
+    limits: LimitsConfig,
+    dispute_policy: DisputePolicy,
+    clock: Box<dyn Clock>,
+    /// Deadlines of open disputes, earliest first.
+    ///
+    /// Entries of disputes closed before their deadline are discarded lazily.
+    dispute_deadlines: BinaryHeap<Reverse<DisputeDeadline>>,
+    /// Where each deposit and withdrawal ID is in its client's history.
+    ///
+    /// Points to the accepted transaction with the ID, if any, or the first rejected one.
+    tx_index: HashMap<u32, TxLocation>,
+    /// The rows processed so far, if idempotency is enabled.
+    replays: Option<ReplayGuard>,
+    /// Whether to reject transactions older than a previous one of their client.
+    strict_ordering: bool,
+    /// Counters of the transactions processed, without the parts computed on demand.
+    stats: EngineStats,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TxLocation {
+    client: u16,
+    position: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct DisputeDeadline {
+    deadline: Timestamp,
+    client: u16,
+    tx: u32,
+    opened_at: Timestamp,
 }
 
 impl Default for Engine {
@@ -26,23 +113,367 @@ impl Default for Engine {
         let mut this = Self {
             clients: vec![],
             seem_clients: BitSet::default(),
+            limits: LimitsConfig::default(),
+            dispute_policy: DisputePolicy::default(),
+            clock: Box::new(SystemClock),
+            dispute_deadlines: BinaryHeap::new(),
+            tx_index: HashMap::new(),
+            replays: None,
+            strict_ordering: false,
+            stats: EngineStats::default(),
         };
 
-        this.seem_clients.reserve_len(u16::MAX as _);
-        this.clients.resize_with(u16::MAX as _, Client::default);
+        this.seem_clients.reserve_len(CLIENTS);
+        this.clients.resize_with(CLIENTS, Client::default);
 
         this
     }
 }
 
 impl Engine {
+    /// Enforce the given limits on clients' transactions.
+    pub fn with_limits(mut self, limits: LimitsConfig) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Apply the given policy to disputes.
+    pub fn with_dispute_policy(mut self, policy: DisputePolicy) -> Self {
+        self.dispute_policy = policy;
+        self
+    }
+
+    /// Use the given clock to timestamp transactions without a timestamp.
+    ///
+    /// By default, the system's wall clock is used.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Ignore transactions that are exact copies of already processed ones, as sent again
+    /// when a batch is retried, rejecting them with [`Rejection::Replayed`] without any
+    /// effect.
+    ///
+    /// Copies are recognized by their type, client, transaction ID and amount. Deposits and
+    /// withdrawals reusing an ID with other contents are rejected with
+    /// [`Rejection::ConflictingTransaction`].
+    ///
+    /// Disputes, resolves and chargebacks are only copies while the dispute is still in
+    /// the state the original left it in, so that the dispute lifecycle can go on, e.g.
+    /// a resolved dispute can be reopened by the same dispute row. Repeated partial
+    /// disputes, resolves or chargebacks of the same amount in a row are still
+    /// considered copies.
+    pub fn with_idempotency(mut self) -> Self {
+        self.replays = Some(ReplayGuard::default());
+        self
+    }
+
+    /// Reject transactions whose timestamp is older than a previous one of the same
+    /// client with [`Rejection::OutOfOrder`].
+    ///
+    /// By default, such transactions are applied as they come and only counted in
+    /// [`EngineStats::out_of_order`].
+    pub fn with_strict_ordering(mut self) -> Self {
+        self.strict_ordering = true;
+        self
+    }
+
+    /// Time the processing of each transaction, for the latency histogram of
+    /// [`Engine::stats`].
+    ///
+    /// Off by default, as it reads the monotonic clock twice per transaction.
+    pub fn with_timing(mut self) -> Self {
+        self.stats.latency = Some(Histogram::new(Histogram::LATENCY_SECS));
+        self
+    }
+
     /// Process a transaction.
     ///
     /// It will route the transaction to the appropriate client based on the client ID
     /// in the transaction.
-    pub fn process_transaction(&mut self, tx: Transaction) {
+    ///
+    /// # Errors
+    ///
+    /// Returns the reason the transaction was rejected, in which case the client's
+    /// account is left untouched.
+    pub fn process_transaction(&mut self, tx: Transaction) -> Result<(), Rejection> {
+        let start = self.stats.latency.is_some().then(Instant::now);
+        let result = self.apply(tx);
+        if let Some(replays) = &mut self.replays
+            && result != Err(Rejection::Replayed)
+        {
+            replays.record(&tx, self.clients[tx.client as usize].dispute_state(tx.id));
+        }
+        self.stats.record(tx.kind(), result);
+        if let (Some(start), Some(latency)) = (start, &mut self.stats.latency) {
+            latency.observe(start.elapsed().as_secs_f64());
+        }
+        result
+    }
+
+    /// Whether the transaction's timestamp is older than a previous one of its client.
+    pub fn is_out_of_order(&self, tx: &Transaction) -> bool {
+        self.clients[tx.client as usize].is_out_of_order(tx)
+    }
+
+    fn apply(&mut self, tx: Transaction) -> Result<(), Rejection> {
+        if let Some(replays) = &mut self.replays {
+            let dispute = self.clients[tx.client as usize].dispute_state(tx.id);
+            replays.check(&tx, dispute)?;
+        }
+
+        let now = tx.timestamp.unwrap_or_else(|| self.clock.now());
+        self.advance_time(now);
+
         self.seem_clients.insert(tx.client as _);
-        self.clients[tx.client as usize].process_transaction(tx);
+        let limits = self.limits.for_client(tx.client);
+        let client = &mut self.clients[tx.client as usize];
+        let dispute = client.dispute_state(tx.id);
+        let out_of_order = client.is_out_of_order(&tx);
+        self.stats.out_of_order += out_of_order as u64;
+        let result = if out_of_order && self.strict_ordering {
+            client.record_outcome(tx, now, Err(Rejection::OutOfOrder));
+            Err(Rejection::OutOfOrder)
+        } else {
+            client.post_transaction(tx, limits, &self.dispute_policy, now)
+        };
+        if let Ok(posting) = &result {
+            self.stats.record_postings(posting.as_slice());
+            if tx.deposited_amount().is_none() && tx.withdrawn_amount().is_none() {
+                self.stats
+                    .record_dispute(tx.kind(), dispute, client.dispute_state(tx.id));
+            }
+        }
+        if tx.deposited_amount().is_some() || tx.withdrawn_amount().is_some() {
+            self.index_transaction(&tx, result.is_ok());
+        }
+        result?;
+
+        if let TxPayload::Dispute { .. } = tx.payload
+            && let Some(deadline) = self.dispute_policy.deadline
+        {
+            self.dispute_deadlines.push(Reverse(DisputeDeadline {
+                deadline: now.saturating_add(deadline),
+                client: tx.client,
+                tx: tx.id,
+                opened_at: now,
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Move the engine's notion of time forward to `now`.
+    ///
+    /// Disputes open past their deadline are closed according to the dispute policy's
+    /// [`ExpiryAction`]. This is done automatically when processing transactions, but
+    /// can be called to expire disputes when no transactions arrive.
+    pub fn advance_time(&mut self, now: Timestamp) {
+        while let Some(Reverse(next)) = self.dispute_deadlines.peek()
+            && next.deadline <= now
+        {
+            let Reverse(expired) = self.dispute_deadlines.pop().unwrap();
+            let client = &mut self.clients[expired.client as usize];
+            let dispute = client.dispute_state(expired.tx);
+            let posting = client.expire_dispute(
+                expired.tx,
+                expired.opened_at,
+                &self.dispute_policy,
+                expired.deadline,
+            );
+            self.stats.record_postings(posting.as_slice());
+            let kind = match self.dispute_policy.on_expiry {
+                ExpiryAction::Resolve => TransactionKind::Resolve,
+                ExpiryAction::Chargeback => TransactionKind::Chargeback,
+            };
+            self.stats
+                .record_dispute(kind, dispute, client.dispute_state(expired.tx));
+        }
+    }
+
+    /// Point the transaction's ID to the client's latest history entry, unless an
+    /// accepted transaction already has the ID.
+    fn index_transaction(&mut self, tx: &Transaction, accepted: bool) {
+        let location = TxLocation {
+            client: tx.client,
+            position: self.clients[tx.client as usize].history().len() - 1,
+        };
+
+        match self.tx_index.entry(tx.id) {
+            Entry::Vacant(entry) => {
+                entry.insert(location);
+            }
+            Entry::Occupied(mut entry) => {
+                let TxLocation { client, position } = *entry.get();
+                let indexed = &self.clients[client as usize].history()[position];
+                if accepted && indexed.outcome.is_err() {
+                    entry.insert(location);
+                }
+            }
+        }
+    }
+
+    /// The deposit or withdrawal with the given ID and its current status.
+    ///
+    /// If several transactions used the ID, the accepted one is returned, or the first
+    /// one if all were rejected.
+    pub fn transaction(&self, id: u32) -> Option<TransactionRecord<'_>> {
+        let TxLocation { client, position } = *self.tx_index.get(&id)?;
+        Some(self.clients[client as usize].record(position))
+    }
+
+    /// Up to `limit` transactions processed for the client, in processing order,
+    /// skipping the first `offset` ones.
+    ///
+    /// Includes rejected transactions as well as disputes, resolves and chargebacks.
+    pub fn client_transactions(
+        &self,
+        client: u16,
+        offset: usize,
+        limit: usize,
+    ) -> impl ExactSizeIterator<Item = TransactionRecord<'_>> {
+        let client = &self.clients[client as usize];
+        let len = client.history().len();
+        let start = offset.min(len);
+        let end = start.saturating_add(limit).min(len);
+
+        (start..end).map(|position| client.record(position))
+    }
+
+    /// The client's disputes pending a resolution, oldest first.
+    pub fn open_disputes(&self, client: u16) -> impl Iterator<Item = DisputeRecord<'_>> {
+        self.clients[client as usize].open_disputes().into_iter()
+    }
+
+    /// The state of the dispute of the deposit with the given ID, if it was ever
+    /// disputed.
+    pub fn dispute_status(&self, tx: u32) -> Option<DisputeState> {
+        let TxLocation { client, .. } = *self.tx_index.get(&tx)?;
+        self.clients[client as usize].dispute_state(tx)
+    }
+
+    /// The statement of the client's postings in `[from, to)`.
+    ///
+    /// Postings are derived from the client's whole history on each call.
+    pub fn statement(&self, client: u16, from: Timestamp, to: Timestamp) -> Statement {
+        Statement::new(client, from, to, &self.clients[client as usize].postings())
+    }
+
+    /// The double-entry journal entries of the client's postings, in processing order.
+    pub fn journal(&self, client: u16) -> impl Iterator<Item = JournalEntry> + '_ {
+        self.clients[client as usize]
+            .postings()
+            .into_iter()
+            .map(move |posting| JournalEntry::new(client, &posting))
+    }
+
+    /// The ledger with the journal entries of all clients.
+    pub fn ledger(&self) -> Ledger {
+        let mut ledger = Ledger::default();
+        for client in self.seem_clients.iter() {
+            for entry in self.journal(client as u16) {
+                ledger.post(&entry);
+            }
+        }
+        ledger
+    }
+
+    /// Check the invariants of every account and reconcile the flow of funds with the
+    /// engine's holdings.
+    ///
+    /// Invariants only break due to bugs, so any discrepancy found should be reported.
+    pub fn verify(&self) -> Reconciliation {
+        let ledger = self.ledger();
+        let mut reconciliation = Reconciliation::default();
+        let mut discrepancies = vec![];
+
+        for client_id in self.seem_clients.iter() {
+            let client_id = client_id as u16;
+            let client = &self.clients[client_id as usize];
+            let account = client.account();
+            let (available, held) = (account.available_funds(), account.held_funds());
+
+            if available < Amount::ZERO && !self.dispute_policy.allow_deficit {
+                discrepancies.push(Discrepancy::NegativeAvailable {
+                    client: client_id,
+                    available,
+                });
+            }
+            if held < Amount::ZERO {
+                discrepancies.push(Discrepancy::NegativeHeld {
+                    client: client_id,
+                    held,
+                });
+            }
+
+            let disputes = client.disputes_held();
+            if held != disputes {
+                discrepancies.push(Discrepancy::HeldMismatch {
+                    client: client_id,
+                    held,
+                    disputes,
+                });
+            }
+
+            let mut charged_back = false;
+            for posting in client.postings() {
+                charged_back |= posting.kind == PostingKind::Chargeback;
+                reconciliation.add(posting.kind, posting.amount);
+            }
+            if account.is_locked() != charged_back {
+                discrepancies.push(Discrepancy::LockMismatch {
+                    client: client_id,
+                    locked: account.is_locked(),
+                });
+            }
+
+            let balances = Balances { available, held };
+            let ledger_balances = ledger.client_balances(client_id);
+            if balances != ledger_balances {
+                discrepancies.push(Discrepancy::LedgerMismatch {
+                    client: client_id,
+                    account: balances,
+                    ledger: ledger_balances,
+                });
+            }
+
+            reconciliation.holdings += account.total_funds();
+        }
+
+        let expected = reconciliation.expected_holdings();
+        if reconciliation.holdings != expected {
+            discrepancies.push(Discrepancy::HoldingsMismatch {
+                expected,
+                actual: reconciliation.holdings,
+            });
+        }
+
+        reconciliation.discrepancies = discrepancies;
+        reconciliation
+    }
+
+    /// Statistics about the transactions processed so far and the engine's state.
+    ///
+    /// Counters are kept while processing, while the number of clients and the memory
+    /// estimate are computed on each call, in time linear in the number of clients.
+    pub fn stats(&self) -> EngineStats {
+        let mut stats = self.stats.clone();
+        stats.peak_memory = size_of::<Self>()
+            + self.clients.capacity() * size_of::<Client>()
+            + self.seem_clients.capacity() / 8
+            + self.dispute_deadlines.capacity() * size_of::<Reverse<DisputeDeadline>>()
+            + stats::table_size::<(u32, TxLocation)>(self.tx_index.capacity())
+            + self.replays.as_ref().map_or(0, ReplayGuard::heap_size);
+
+        for (client_id, account) in self.accounts() {
+            stats.clients += 1;
+            stats.locked_accounts += account.is_locked() as usize;
+            stats.open_disputes += self.clients[client_id as usize].open_dispute_count();
+            stats.held += account.held_funds();
+            stats.peak_memory += self.clients[client_id as usize].heap_size();
+        }
+        stats
     }
 
     /// All client accounts in the engine.
@@ -65,6 +496,7 @@ mod tests {
             Transaction {
                 id: 1,
                 client: 1,
+                timestamp: None,
                 payload: transaction::TxPayload::Deposit {
                     amount: Amount::from(100).rescale(4),
                 },
@@ -72,6 +504,7 @@ mod tests {
             Transaction {
                 id: 2,
                 client: 2,
+                timestamp: None,
                 payload: transaction::TxPayload::Deposit {
                     amount: Amount::from(200).rescale(4),
                 },
@@ -79,6 +512,7 @@ mod tests {
             Transaction {
                 id: 3,
                 client: 1,
+                timestamp: None,
                 payload: transaction::TxPayload::Withdrawal {
                     amount: Amount::from(50).rescale(4),
                 },
@@ -86,7 +520,7 @@ mod tests {
         ];
 
         for tx in txs {
-            engine.process_transaction(tx);
+            engine.process_transaction(tx).unwrap();
         }
 
         let acc1 = engine.clients[1].account();
@@ -98,4 +532,561 @@ mod tests {
         let counts = engine.accounts().count();
         assert_eq!(counts, 2);
     }
+
+    #[test]
+    fn test_per_client_limits() {
+        let limits = LimitsConfig::new(Limits {
+            max_withdrawal: Some(Amount::from(10)),
+            ..Limits::default()
+        })
+        .with_client(
+            2,
+            Limits {
+                max_withdrawal: Some(Amount::from(100)),
+                ..Limits::default()
+            },
+        );
+        let mut engine = Engine::default().with_limits(limits);
+
+        for client in [1, 2] {
+            let deposit = Transaction {
+                id: client as u32,
+                client,
+                timestamp: None,
+                payload: transaction::TxPayload::Deposit {
+                    amount: Amount::from(100).rescale(4),
+                },
+            };
+            engine.process_transaction(deposit).unwrap();
+
+            let withdrawal = Transaction {
+                id: 10 + client as u32,
+                client,
+                timestamp: None,
+                payload: transaction::TxPayload::Withdrawal {
+                    amount: Amount::from(50).rescale(4),
+                },
+            };
+            let expected = if client == 1 {
+                Err(Rejection::LimitExceeded(Limit::Withdrawal))
+            } else {
+                Ok(())
+            };
+            assert_eq!(engine.process_transaction(withdrawal), expected);
+        }
+    }
+
+    #[test]
+    fn test_injected_clock() {
+        let limits = LimitsConfig::new(Limits {
+            max_daily_withdrawal: Some(Amount::from(10)),
+            ..Limits::default()
+        });
+        let now = std::rc::Rc::new(std::cell::Cell::new(Timestamp::UNIX_EPOCH));
+        let mut engine = Engine::default().with_limits(limits).with_clock({
+            let now = now.clone();
+            move || now.get()
+        });
+
+        let tx = |id, payload| Transaction {
+            id,
+            client: 1,
+            payload,
+            timestamp: None,
+        };
+        let withdrawal = transaction::TxPayload::Withdrawal {
+            amount: Amount::from(10),
+        };
+
+        engine
+            .process_transaction(tx(
+                1,
+                transaction::TxPayload::Deposit {
+                    amount: Amount::from(100),
+                },
+            ))
+            .unwrap();
+        assert_eq!(engine.process_transaction(tx(2, withdrawal)), Ok(()));
+        assert_eq!(
+            engine.process_transaction(tx(3, withdrawal)),
+            Err(Rejection::LimitExceeded(Limit::DailyWithdrawal))
+        );
+
+        now.set(Timestamp::from_millis(time::MILLIS_PER_DAY));
+        assert_eq!(engine.process_transaction(tx(3, withdrawal)), Ok(()));
+    }
+
+    #[test]
+    fn test_dispute_expiry() {
+        let policy = DisputePolicy {
+            deadline: Some(std::time::Duration::from_secs(10)),
+            on_expiry: ExpiryAction::Chargeback,
+            ..DisputePolicy::default()
+        };
+        let mut engine = Engine::default().with_dispute_policy(policy);
+
+        let tx = |id, client, payload, millis| Transaction {
+            id,
+            client,
+            payload,
+            timestamp: Some(Timestamp::from_millis(millis)),
+        };
+        let deposit = TxPayload::Deposit {
+            amount: Amount::from(10),
+        };
+
+        for (client, id) in [(1, 1), (2, 2)] {
+            engine
+                .process_transaction(tx(id, client, deposit, 0))
+                .unwrap();
+        }
+        engine
+            .process_transaction(tx(1, 1, TxPayload::Dispute { amount: None }, 1_000))
+            .unwrap();
+        engine
+            .process_transaction(tx(2, 2, TxPayload::Dispute { amount: None }, 2_000))
+            .unwrap();
+        // Client 2 disputes again after resolving, so the first deadline is stale.
+        engine
+            .process_transaction(tx(2, 2, TxPayload::Resolve { amount: None }, 3_000))
+            .unwrap();
+        engine
+            .process_transaction(tx(2, 2, TxPayload::Dispute { amount: None }, 5_000))
+            .unwrap();
+
+        engine.advance_time(Timestamp::from_millis(12_000));
+        assert!(engine.clients[1].account().is_locked());
+        assert!(!engine.clients[2].account().is_locked());
+        assert_eq!(engine.clients[2].account().held_funds(), Amount::from(10));
+
+        // A transaction timestamp also moves time forward.
+        engine
+            .process_transaction(tx(3, 3, deposit, 15_000))
+            .unwrap();
+        assert!(engine.clients[2].account().is_locked());
+        assert_eq!(engine.clients[2].account().total_funds(), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_history_queries() {
+        let mut engine = Engine::default();
+
+        let tx = |id, client, payload, millis| Transaction {
+            id,
+            client,
+            payload,
+            timestamp: Some(Timestamp::from_millis(millis)),
+        };
+        let deposit = TxPayload::Deposit {
+            amount: Amount::from(10),
+        };
+        let withdrawal = TxPayload::Withdrawal {
+            amount: Amount::from(100),
+        };
+
+        let txs = [
+            tx(1, 1, deposit, 0),
+            tx(2, 1, deposit, 1),
+            tx(3, 1, withdrawal, 2),
+            // Rejected for client 2, so tx 1 still points to client 1's deposit.
+            tx(1, 2, withdrawal, 3),
+            tx(2, 1, TxPayload::Dispute { amount: None }, 4),
+            tx(1, 1, TxPayload::Dispute { amount: None }, 5),
+            tx(1, 1, TxPayload::Resolve { amount: None }, 6),
+        ];
+        for tx in txs {
+            let _ = engine.process_transaction(tx);
+        }
+
+        let record = engine.transaction(1).unwrap();
+        assert_eq!(record.transaction().client(), 1);
+        assert_eq!(record.status(), TxStatus::Disputed(DisputeState::Resolved));
+        assert_eq!(
+            engine.transaction(3).unwrap().status(),
+            TxStatus::Rejected(Rejection::InsufficientFunds)
+        );
+        assert!(engine.transaction(4).is_none());
+
+        assert_eq!(engine.dispute_status(2), Some(DisputeState::Open));
+        assert_eq!(engine.dispute_status(3), None);
+
+        let open: Vec<_> = engine.open_disputes(1).collect();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].transaction().id(), 2);
+        assert_eq!(open[0].held(), Amount::from(10));
+
+        let page: Vec<_> = engine.client_transactions(1, 2, 3).collect();
+        let kinds: Vec<_> = page.iter().map(|r| r.transaction().kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                TransactionKind::Withdrawal,
+                TransactionKind::Dispute,
+                TransactionKind::Dispute
+            ]
+        );
+        assert_eq!(page[2].status(), TxStatus::Accepted);
+        assert_eq!(engine.client_transactions(1, 6, 10).len(), 0);
+        assert_eq!(engine.client_transactions(2, 0, 10).len(), 1);
+    }
+
+    #[test]
+    fn test_ledger_matches_accounts() {
+        let policy = DisputePolicy {
+            allow_deficit: true,
+            transitions: DisputeTransitions::ALL,
+            ..DisputePolicy::default()
+        };
+        let mut engine = Engine::default().with_dispute_policy(policy);
+
+        let tx = |id, client, payload| Transaction {
+            id,
+            client,
+            payload,
+            timestamp: Some(Timestamp::UNIX_EPOCH),
+        };
+        let amount = |amount: i32| Amount::from(amount);
+        let txs = [
+            tx(
+                1,
+                1,
+                TxPayload::Deposit {
+                    amount: amount(100),
+                },
+            ),
+            tx(2, 1, TxPayload::Withdrawal { amount: amount(80) }),
+            tx(1, 1, TxPayload::Dispute { amount: None }),
+            tx(1, 1, TxPayload::Chargeback { amount: None }),
+            tx(1, 1, TxPayload::Dispute { amount: None }),
+            tx(1, 1, TxPayload::Resolve { amount: None }),
+            tx(3, 2, TxPayload::Deposit { amount: amount(50) }),
+            tx(
+                3,
+                2,
+                TxPayload::Dispute {
+                    amount: Some(amount(20)),
+                },
+            ),
+        ];
+        for tx in txs {
+            engine.process_transaction(tx).unwrap();
+        }
+
+        let ledger = engine.ledger();
+        for (client, account) in engine.accounts() {
+            let balances = ledger.client_balances(client);
+            assert_eq!(balances.available, account.available_funds());
+            assert_eq!(balances.held, account.held_funds());
+        }
+
+        let trial_balance = ledger.trial_balance();
+        let balance = |account| {
+            trial_balance
+                .rows
+                .iter()
+                .find(|row| row.account == account)
+                .map(TrialBalanceRow::balance)
+        };
+        assert_eq!(balance(LedgerAccount::HouseCash), Some(amount(70)));
+
+        let reconciliation = engine.verify();
+        assert!(reconciliation.is_ok(), "{reconciliation}");
+        assert_eq!(reconciliation.holdings, amount(70));
+        assert_eq!(reconciliation.expected_holdings(), amount(70));
+    }
+
+    #[test]
+    fn test_highest_client_id() {
+        let mut engine = Engine::default();
+        let deposit = Transaction {
+            id: 1,
+            client: u16::MAX,
+            timestamp: None,
+            payload: TxPayload::Deposit {
+                amount: Amount::from(10),
+            },
+        };
+
+        engine.process_transaction(deposit).unwrap();
+        let (client, account) = engine.accounts().next().unwrap();
+        assert_eq!(client, u16::MAX);
+        assert_eq!(account.available_funds(), Amount::from(10));
+    }
+
+    #[test]
+    fn test_idempotent_replays() {
+        let mut engine = Engine::default().with_idempotency();
+
+        let tx = |id, client, payload| Transaction {
+            id,
+            client,
+            payload,
+            timestamp: None,
+        };
+        let deposit = |amount| TxPayload::Deposit {
+            amount: Amount::from(amount),
+        };
+        let withdrawal = TxPayload::Withdrawal {
+            amount: Amount::from(15),
+        };
+
+        // The withdrawal is rejected, and stays so when resent after more funds arrived.
+        let batch = [tx(1, 1, deposit(10)), tx(2, 1, withdrawal)];
+        assert_eq!(engine.process_transaction(batch[0]), Ok(()));
+        assert_eq!(
+            engine.process_transaction(batch[1]),
+            Err(Rejection::InsufficientFunds)
+        );
+        engine.process_transaction(tx(3, 1, deposit(10))).unwrap();
+        for tx in batch {
+            assert_eq!(engine.process_transaction(tx), Err(Rejection::Replayed));
+        }
+
+        let dispute = tx(1, 1, TxPayload::Dispute { amount: None });
+        engine.process_transaction(dispute).unwrap();
+        assert_eq!(
+            engine.process_transaction(dispute),
+            Err(Rejection::Replayed)
+        );
+
+        // Same amount with a rescaled representation is still a copy.
+        let rescaled = TxPayload::Deposit {
+            amount: Amount::from(10).rescale(4),
+        };
+        let rescaled = tx(3, 1, rescaled);
+        assert_eq!(
+            engine.process_transaction(rescaled),
+            Err(Rejection::Replayed)
+        );
+        for conflicting in [
+            tx(3, 1, deposit(11)),
+            tx(3, 2, deposit(10)),
+            tx(2, 1, deposit(15)),
+        ] {
+            assert_eq!(
+                engine.process_transaction(conflicting),
+                Err(Rejection::ConflictingTransaction)
+            );
+        }
+
+        let account = engine.clients[1].account();
+        assert_eq!(account.available_funds(), Amount::from(10));
+        assert_eq!(account.held_funds(), Amount::from(10));
+        assert_eq!(engine.client_transactions(1, 0, 10).len(), 4);
+        assert_eq!(engine.accounts().count(), 1);
+    }
+
+    #[test]
+    fn test_idempotent_reopened_dispute() {
+        let mut engine = Engine::default().with_idempotency();
+        let tx = |id, payload| Transaction {
+            id,
+            client: 1,
+            payload,
+            timestamp: None,
+        };
+        let deposit = tx(
+            1,
+            TxPayload::Deposit {
+                amount: Amount::from(10),
+            },
+        );
+        let dispute = tx(1, TxPayload::Dispute { amount: None });
+        let resolve = tx(1, TxPayload::Resolve { amount: None });
+
+        engine.process_transaction(deposit).unwrap();
+        engine.process_transaction(dispute).unwrap();
+        engine.process_transaction(resolve).unwrap();
+        assert_eq!(
+            engine.process_transaction(resolve),
+            Err(Rejection::Replayed)
+        );
+
+        // The same rows move the reopened dispute along its lifecycle.
+        engine.process_transaction(dispute).unwrap();
+        assert_eq!(engine.dispute_status(1), Some(DisputeState::Reopened));
+        assert_eq!(
+            engine.process_transaction(dispute),
+            Err(Rejection::Replayed)
+        );
+        engine.process_transaction(resolve).unwrap();
+        assert_eq!(engine.dispute_status(1), Some(DisputeState::Arbitrated));
+
+        let account = engine.clients[1].account();
+        assert_eq!(account.available_funds(), Amount::from(10));
+        assert_eq!(account.held_funds(), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut engine = Engine::default().with_idempotency().with_timing();
+        let tx = |id, client, payload| Transaction {
+            id,
+            client,
+            payload,
+            timestamp: None,
+        };
+        let deposit = |amount| TxPayload::Deposit {
+            amount: Amount::from(amount),
+        };
+
+        for tx in [
+            tx(1, 1, deposit(10)),
+            tx(1, 1, deposit(10)),
+            tx(2, 2, deposit(5)),
+            tx(
+                3,
+                2,
+                TxPayload::Withdrawal {
+                    amount: Amount::from(8),
+                },
+            ),
+            tx(1, 1, TxPayload::Dispute { amount: None }),
+            tx(1, 1, TxPayload::Chargeback { amount: None }),
+            tx(2, 2, TxPayload::Dispute { amount: None }),
+            tx(2, 2, TxPayload::Resolve { amount: None }),
+        ] {
+            let _ = engine.process_transaction(tx);
+        }
+
+        let stats = engine.stats();
+        assert_eq!(
+            (
+                stats.total_accepted(),
+                stats.total_rejected(),
+                stats.total_ignored()
+            ),
+            (6, 1, 1)
+        );
+        assert_eq!(stats.accepted(TransactionKind::Deposit), 2);
+        assert_eq!(stats.rejected(TransactionKind::Withdrawal), 1);
+        assert_eq!(stats.ignored(TransactionKind::Deposit), 1);
+        assert_eq!(stats.rejections(Rejection::Replayed), 0);
+        assert_eq!(stats.rejections(Rejection::InsufficientFunds), 1);
+        assert_eq!(stats.volume(PostingKind::Deposit), Amount::from(15));
+        assert_eq!(stats.volume(PostingKind::Chargeback), Amount::from(10));
+        assert_eq!(
+            (
+                stats.disputes_opened,
+                stats.disputes_resolved,
+                stats.disputes_charged_back
+            ),
+            (2, 1, 1)
+        );
+        assert_eq!((stats.clients, stats.locked_accounts), (2, 1));
+        assert_eq!((stats.open_disputes, stats.held), (0, Amount::ZERO));
+        assert_eq!(stats.latency.as_ref().map(Histogram::count), Some(8));
+        assert!(stats.peak_memory > CLIENTS * size_of::<Client>());
+
+        let mut prometheus = vec![];
+        stats.write_prometheus(&mut prometheus).unwrap();
+        let prometheus = String::from_utf8(prometheus).unwrap();
+        for line in [
+            "payment_engine_transactions_total{type=\"deposit\",outcome=\"accepted\"} 2",
+            "payment_engine_transactions_total{type=\"chargeback\",outcome=\"rejected\"} 0",
+            "payment_engine_transactions_total{type=\"deposit\",outcome=\"ignored\"} 1",
+            "payment_engine_rejections_total{reason=\"insufficient_funds\",severity=\"normal\"} 1",
+            "payment_engine_volume_total{kind=\"deposit\"} 15",
+            "payment_engine_disputes_total{event=\"charged_back\"} 1",
+            "payment_engine_locked_accounts 1",
+        ] {
+            assert!(prometheus.lines().any(|l| l == line), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_stats_dispute_transitions() {
+        let policy = DisputePolicy {
+            transitions: DisputeTransitions::ALL,
+            ..DisputePolicy::default()
+        };
+        let mut engine = Engine::default().with_dispute_policy(policy);
+        let tx = |id, client, payload| Transaction {
+            id,
+            client,
+            payload,
+            timestamp: None,
+        };
+        let partial = || TxPayload::Dispute {
+            amount: Some(Amount::from(5)),
+        };
+
+        for tx in [
+            tx(
+                1,
+                1,
+                TxPayload::Deposit {
+                    amount: Amount::from(100),
+                },
+            ),
+            tx(1, 1, TxPayload::Dispute { amount: None }),
+            tx(1, 1, TxPayload::Chargeback { amount: None }),
+            // Representment reopens the charged back dispute, and arbitration resolves it.
+            tx(1, 1, TxPayload::Dispute { amount: None }),
+            tx(1, 1, TxPayload::Resolve { amount: None }),
+            tx(
+                2,
+                2,
+                TxPayload::Deposit {
+                    amount: Amount::from(20),
+                },
+            ),
+            // Disputing more of an open dispute doesn't open another one.
+            tx(2, 2, partial()),
+            tx(2, 2, partial()),
+            tx(2, 2, partial()),
+            tx(2, 2, TxPayload::Resolve { amount: None }),
+        ] {
+            engine.process_transaction(tx).unwrap();
+        }
+
+        let stats = engine.stats();
+        assert_eq!(
+            (
+                stats.disputes_opened,
+                stats.disputes_resolved,
+                stats.disputes_charged_back
+            ),
+            (3, 2, 1)
+        );
+        assert_eq!(
+            stats.volume(PostingKind::ChargebackReversal),
+            Amount::from(100)
+        );
+    }
+
+    #[test]
+    fn test_strict_ordering() {
+        let tx = |id, millis| Transaction {
+            id,
+            client: 1,
+            payload: TxPayload::Deposit {
+                amount: Amount::from(1),
+            },
+            timestamp: Some(Timestamp::from_millis(millis)),
+        };
+
+        let mut engine = Engine::default();
+        engine.process_transaction(tx(1, 2_000)).unwrap();
+        assert!(engine.is_out_of_order(&tx(2, 1_000)));
+        assert_eq!(engine.process_transaction(tx(2, 1_000)), Ok(()));
+        assert_eq!(engine.stats().out_of_order, 1);
+        assert_eq!(
+            engine.clients[1].account().available_funds(),
+            Amount::from(2)
+        );
+
+        let mut engine = Engine::default().with_strict_ordering();
+        engine.process_transaction(tx(1, 2_000)).unwrap();
+        assert_eq!(
+            engine.process_transaction(tx(2, 1_000)),
+            Err(Rejection::OutOfOrder)
+        );
+        assert_eq!(engine.process_transaction(tx(3, 2_000)), Ok(()));
+        assert_eq!(engine.stats().out_of_order, 1);
+        assert_eq!(
+            engine.clients[1].account().available_funds(),
+            Amount::from(2)
+        );
+    }
 }
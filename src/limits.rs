@@ -0,0 +1,331 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    path::Path,
+    time::Duration,
+};
+
+use crate::{Amount, Limit, Rejection, Timestamp};
+
+/// Limits applied to a single client's transactions.
+///
+/// Limits that are not set are not enforced. Amounts must be written as strings
+/// in configuration files (e.g. `max_withdrawal = "1000.00"`) to avoid float
+/// rounding.
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    /// Maximum amount of a single withdrawal.
+    pub max_withdrawal: Option<Amount>,
+    /// Maximum sum of withdrawals in a single UTC day.
+    pub max_daily_withdrawal: Option<Amount>,
+    /// Maximum number of transactions accepted in a sliding time window.
+    pub max_transactions: Option<RateLimit>,
+    /// Maximum amount of funds held due to disputes.
+    pub max_held: Option<Amount>,
+}
+
+/// A maximum number of transactions allowed in a sliding time window.
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Maximum number of transactions in the window.
+    pub count: u32,
+    /// Length of the window.
+    #[serde(rename = "window_secs", deserialize_with = "deserialize_secs")]
+    pub window: Duration,
+}
+
+fn deserialize_secs<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let secs = <u64 as serde::Deserialize>::deserialize(deserializer)?;
+    Ok(Duration::from_secs(secs))
+}
+
+impl Limits {
+    /// Fill every limit not set in `self` with the one in `fallback`.
+    fn or(self, fallback: &Limits) -> Limits {
+        Limits {
+            max_withdrawal: self.max_withdrawal.or(fallback.max_withdrawal),
+            max_daily_withdrawal: self.max_daily_withdrawal.or(fallback.max_daily_withdrawal),
+            max_transactions: self.max_transactions.or(fallback.max_transactions),
+            max_held: self.max_held.or(fallback.max_held),
+        }
+    }
+}
+
+/// Default limits for all clients, with optional per-client overrides.
+///
+/// It can be loaded from a TOML file with the following layout:
+///
+/// ```toml
+/// [default]
+/// max_withdrawal = "1000"
+/// max_daily_withdrawal = "5000"
+/// max_transactions = { count = 10, window_secs = 1 }
+/// max_held = "10000"
+///
+/// # Overrides for client 42, unset limits fall back to `default`.
+/// [clients.42]
+/// max_withdrawal = "50000"
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct LimitsConfig {
+    default: Limits,
+    // Overrides are already merged with `default`.
+    clients: HashMap<u16, Limits>,
+}
+
+impl LimitsConfig {
+    pub fn new(default: Limits) -> Self {
+        Self {
+            default,
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Override the limits of a single client.
+    ///
+    /// Limits not set in `limits` fall back to the default ones.
+    pub fn with_client(mut self, client: u16, limits: Limits) -> Self {
+        let limits = limits.or(&self.default);
+        self.clients.insert(client, limits);
+        self
+    }
+
+    /// Parse the configuration from a TOML string.
+    pub fn from_toml_str(s: &str) -> Result<Self, ConfigError> {
+        #[derive(serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Raw {
+            #[serde(default)]
+            default: Limits,
+            #[serde(default)]
+            clients: HashMap<u16, Limits>,
+        }
+
+        let raw: Raw = toml::from_str(s).map_err(ConfigError::Parse)?;
+
+        Ok(raw
+            .clients
+            .into_iter()
+            .fold(Self::new(raw.default), |this, (client, limits)| {
+                this.with_client(client, limits)
+            }))
+    }
+
+    /// Read and parse the configuration from a TOML file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::from_toml_str(&contents)
+    }
+
+    /// The limits that apply to the given client.
+    pub fn for_client(&self, client: u16) -> &Limits {
+        self.clients.get(&client).unwrap_or(&self.default)
+    }
+}
+
+/// Error loading a configuration file.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read config: {err}"),
+            Self::Parse(err) => write!(f, "invalid config: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+        }
+    }
+}
+
+/// Per-client state needed to evaluate [`Limits`].
+#[derive(Debug, Default)]
+pub(crate) struct LimitTracker {
+    /// The UTC day of the last withdrawal and the sum of withdrawals in it.
+    daily_withdrawal: (i64, Amount),
+    /// Times of the most recent accepted transactions, oldest first.
+    ///
+    /// Only tracked when a rate limit is configured.
+    recent: VecDeque<Timestamp>,
+}
+
+impl LimitTracker {
+    pub(crate) fn heap_size(&self) -> usize {
+        self.recent.capacity() * size_of::<Timestamp>()
+    }
+
+    /// Check if accepting one more transaction would exceed the rate limit.
+    pub(crate) fn check_velocity(
+        &mut self,
+        limits: &Limits,
+        now: Timestamp,
+    ) -> Result<(), Rejection> {
+        let Some(rate) = limits.max_transactions else {
+            return Ok(());
+        };
+
+        let window_start = now.saturating_sub(rate.window);
+        while self.recent.front().is_some_and(|&ts| ts <= window_start) {
+            self.recent.pop_front();
+        }
+
+        if self.recent.len() >= rate.count as usize {
+            Err(Rejection::LimitExceeded(Limit::Velocity))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn check_withdrawal(
+        &self,
+        limits: &Limits,
+        amount: Amount,
+        now: Timestamp,
+    ) -> Result<(), Rejection> {
+        if limits.max_withdrawal.is_some_and(|max| amount > max) {
+            return Err(Rejection::LimitExceeded(Limit::Withdrawal));
+        }
+
+        if let Some(max) = limits.max_daily_withdrawal
+            && self.withdrawn_on(now.day()) + amount > max
+        {
+            return Err(Rejection::LimitExceeded(Limit::DailyWithdrawal));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn check_held(limits: &Limits, held: Amount) -> Result<(), Rejection> {
+        if limits.max_held.is_some_and(|max| held > max) {
+            Err(Rejection::LimitExceeded(Limit::Held))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Record an accepted transaction.
+    pub(crate) fn record(&mut self, limits: &Limits, now: Timestamp) {
+        if limits.max_transactions.is_some() {
+            self.recent.push_back(now);
+        }
+    }
+
+    /// Record an accepted withdrawal.
+    pub(crate) fn record_withdrawal(&mut self, amount: Amount, now: Timestamp) {
+        let day = now.day();
+        self.daily_withdrawal = (day, self.withdrawn_on(day) + amount);
+    }
+
+    fn withdrawn_on(&self, day: i64) -> Amount {
+        match self.daily_withdrawal {
+            (last_day, sum) if last_day == day => sum,
+            _ => Amount::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::MILLIS_PER_DAY;
+
+    #[test]
+    fn test_parse_config() {
+        let config = LimitsConfig::from_toml_str(
+            r#"
+            [default]
+            max_withdrawal = "100"
+            max_transactions = { count = 5, window_secs = 60 }
+
+            [clients.42]
+            max_withdrawal = "1000"
+            max_held = "10"
+            "#,
+        )
+        .unwrap();
+
+        let default = config.for_client(1);
+        assert_eq!(default.max_withdrawal, Some(Amount::from(100)));
+        assert_eq!(default.max_held, None);
+        assert_eq!(default.max_transactions.unwrap().count, 5);
+
+        let client = config.for_client(42);
+        assert_eq!(client.max_withdrawal, Some(Amount::from(1000)));
+        assert_eq!(client.max_held, Some(Amount::from(10)));
+        assert_eq!(
+            client.max_transactions.unwrap().window,
+            Duration::from_secs(60)
+        );
+    }
+
+    #[test]
+    fn test_parse_config_rejects_unknown_limits() {
+        let result = LimitsConfig::from_toml_str("[default]\nmax_deposit = \"10\"");
+        assert!(matches!(result, Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn test_velocity_window_slides() {
+        let limits = Limits {
+            max_transactions: Some(RateLimit {
+                count: 2,
+                window: Duration::from_secs(1),
+            }),
+            ..Limits::default()
+        };
+        let mut tracker = LimitTracker::default();
+
+        for millis in [0, 500] {
+            let now = Timestamp::from_millis(millis);
+            assert!(tracker.check_velocity(&limits, now).is_ok());
+            tracker.record(&limits, now);
+        }
+
+        assert_eq!(
+            tracker.check_velocity(&limits, Timestamp::from_millis(999)),
+            Err(Rejection::LimitExceeded(Limit::Velocity))
+        );
+        assert!(
+            tracker
+                .check_velocity(&limits, Timestamp::from_millis(1_000))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_daily_withdrawal_resets() {
+        let limits = Limits {
+            max_daily_withdrawal: Some(Amount::from(100)),
+            ..Limits::default()
+        };
+        let mut tracker = LimitTracker::default();
+        let today = Timestamp::from_millis(0);
+        let tomorrow = Timestamp::from_millis(MILLIS_PER_DAY);
+
+        tracker.record_withdrawal(Amount::from(80), today);
+        assert_eq!(
+            tracker.check_withdrawal(&limits, Amount::from(30), today),
+            Err(Rejection::LimitExceeded(Limit::DailyWithdrawal))
+        );
+        assert!(
+            tracker
+                .check_withdrawal(&limits, Amount::from(30), tomorrow)
+                .is_ok()
+        );
+    }
+}
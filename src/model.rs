@@ -0,0 +1,180 @@
+//! A reference model of the engine for model-based tests.
+//!
+//! The model implements the default policies as plainly as possible, one client at a
+//! time, so the real engine can be checked against it after every transaction of a
+//! generated sequence. Proptest shrinks failing sequences by dropping transactions,
+//! leading to minimal reproductions.
+
+use std::collections::HashMap;
+
+use proptest::{prelude::*, test_runner::TestCaseError};
+
+use crate::{Amount, DisputeState, Engine, Rejection, Timestamp, Transaction, TxPayload};
+
+#[derive(Debug, Default)]
+pub(crate) struct Model {
+    clients: HashMap<u16, ModelClient>,
+}
+
+#[derive(Debug, Default)]
+struct ModelClient {
+    available: Amount,
+    held: Amount,
+    locked: bool,
+    /// Accepted deposits and withdrawals, with whether they are deposits.
+    txs: HashMap<u32, (bool, Amount)>,
+    disputes: HashMap<u32, DisputeState>,
+}
+
+impl Model {
+    /// Apply a transaction whose dispute-related amounts are all `None`.
+    pub(crate) fn apply(&mut self, tx: &Transaction) -> Result<(), Rejection> {
+        let client = self.clients.entry(tx.client).or_default();
+        if client.locked {
+            return Err(Rejection::AccountLocked);
+        }
+
+        match tx.payload {
+            TxPayload::Deposit { amount } => {
+                match client.txs.get(&tx.id) {
+                    Some(&existing) if existing == (true, amount) => {
+                        return Err(Rejection::DuplicateTransaction);
+                    }
+                    Some(_) => return Err(Rejection::ConflictingTransaction),
+                    None => {}
+                }
+                if amount <= Amount::ZERO {
+                    return Err(Rejection::InvalidAmount);
+                }
+                client.available += amount;
+                client.txs.insert(tx.id, (true, amount));
+            }
+            TxPayload::Withdrawal { amount } => {
+                match client.txs.get(&tx.id) {
+                    Some(&existing) if existing == (false, amount) => {
+                        return Err(Rejection::DuplicateTransaction);
+                    }
+                    Some(_) => return Err(Rejection::ConflictingTransaction),
+                    None => {}
+                }
+                if amount <= Amount::ZERO {
+                    return Err(Rejection::InvalidAmount);
+                }
+                if client.available < amount {
+                    return Err(Rejection::InsufficientFunds);
+                }
+                client.available -= amount;
+                client.txs.insert(tx.id, (false, amount));
+            }
+            TxPayload::Dispute { .. } => {
+                let amount = client.deposit(tx.id, true)?;
+                let next = match client.disputes.get(&tx.id) {
+                    None => DisputeState::Open,
+                    Some(DisputeState::Resolved) => DisputeState::Reopened,
+                    Some(DisputeState::Open | DisputeState::Reopened) => {
+                        return Err(Rejection::AlreadyDisputed);
+                    }
+                    Some(_) => return Err(Rejection::InvalidDisputeTransition),
+                };
+                if client.available < amount {
+                    return Err(Rejection::InsufficientFunds);
+                }
+                client.available -= amount;
+                client.held += amount;
+                client.disputes.insert(tx.id, next);
+            }
+            TxPayload::Resolve { .. } | TxPayload::Chargeback { .. } => {
+                let amount = client.deposit(tx.id, false)?;
+                let resolve = matches!(tx.payload, TxPayload::Resolve { .. });
+                let next = match client.disputes.get(&tx.id) {
+                    Some(DisputeState::Open) if resolve => DisputeState::Resolved,
+                    Some(DisputeState::Open) => DisputeState::ChargedBack,
+                    Some(DisputeState::Reopened) => DisputeState::Arbitrated,
+                    _ => return Err(Rejection::NotDisputed),
+                };
+                client.held -= amount;
+                if resolve {
+                    client.available += amount;
+                } else {
+                    client.locked = true;
+                }
+                client.disputes.insert(tx.id, next);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that every client's account in the engine matches the model.
+    pub(crate) fn check(&self, engine: &Engine) -> Result<(), TestCaseError> {
+        prop_assert_eq!(engine.accounts().count(), self.clients.len());
+
+        for (client_id, account) in engine.accounts() {
+            let client = &self.clients[&client_id];
+            prop_assert_eq!(account.available_funds(), client.available);
+            prop_assert_eq!(account.held_funds(), client.held);
+            prop_assert_eq!(account.is_locked(), client.locked);
+
+            for (&tx, &state) in &client.disputes {
+                prop_assert_eq!(
+                    engine.clients[client_id as usize].dispute_state(tx),
+                    Some(state)
+                );
+            }
+        }
+
+        let reconciliation = engine.verify();
+        prop_assert!(reconciliation.is_ok(), "{}", reconciliation);
+        Ok(())
+    }
+}
+
+impl ModelClient {
+    /// The amount of the deposit referenced by a dispute-related transaction.
+    fn deposit(&self, id: u32, dispute: bool) -> Result<Amount, Rejection> {
+        match self.txs.get(&id) {
+            None => Err(Rejection::UnknownTransaction),
+            Some(&(true, amount)) => Ok(amount),
+            Some(_) if dispute => Err(Rejection::NotDisputable),
+            Some(_) => Err(Rejection::NotDisputed),
+        }
+    }
+}
+
+/// Interleaved transactions of a few clients, plus the highest client ID, over a
+/// handful of IDs so that duplicates and disputes of existing transactions are common.
+pub(crate) fn any_interleaved_txs(max_size: usize) -> impl Strategy<Value = Vec<Transaction>> {
+    let amount = || (1..=100u32).prop_map(Amount::from);
+    let payload = prop_oneof![
+        4 => amount().prop_map(|amount| TxPayload::Deposit { amount }),
+        3 => amount().prop_map(|amount| TxPayload::Withdrawal { amount }),
+        3 => Just(TxPayload::Dispute { amount: None }),
+        2 => Just(TxPayload::Resolve { amount: None }),
+        1 => Just(TxPayload::Chargeback { amount: None }),
+    ];
+    let client = prop_oneof![9 => 0..4u16, 1 => Just(u16::MAX)];
+
+    let tx = (0..12u32, client, payload).prop_map(|(id, client, payload)| Transaction {
+        id,
+        client,
+        payload,
+        timestamp: None,
+    });
+
+    prop::collection::vec(tx, 0..max_size)
+}
+
+proptest! {
+    #[test]
+    fn test_engine_matches_model(txs in any_interleaved_txs(200)) {
+        let mut engine = Engine::default().with_clock(|| Timestamp::UNIX_EPOCH);
+        let mut model = Model::default();
+
+        for (step, tx) in txs.iter().enumerate() {
+            let expected = model.apply(tx);
+            let actual = engine.process_transaction(*tx);
+            prop_assert_eq!(actual, expected, "step {}: {:?}", step, tx);
+            model.check(&engine)?;
+        }
+    }
+}
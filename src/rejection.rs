@@ -0,0 +1,190 @@
+use std::fmt;
+
+use crate::AccountError;
+
+/// Reason why the engine refused to apply a transaction.
+///
+/// Rejected transactions leave the client's account untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rejection {
+    /// The client's account is locked due to a chargeback.
+    AccountLocked,
+    /// A deposit or withdrawal reused the ID of an identical, already processed one.
+    DuplicateTransaction,
+    /// The account does not have enough available funds.
+    InsufficientFunds,
+    /// The referenced transaction does not exist for the client.
+    UnknownTransaction,
+    /// The referenced transaction cannot be disputed, e.g. a withdrawal.
+    NotDisputable,
+    /// The referenced transaction is already under dispute.
+    AlreadyDisputed,
+    /// The referenced transaction is not under dispute.
+    NotDisputed,
+    /// The dispute lifecycle doesn't allow the transaction in the dispute's state.
+    InvalidDisputeTransition,
+    /// The amount isn't positive, or isn't within what can be disputed, resolved or
+    /// charged back.
+    InvalidAmount,
+    /// The referenced transaction is older than the dispute window.
+    DisputeWindowExpired,
+    /// The transaction would exceed one of the client's limits.
+    LimitExceeded(Limit),
+    /// The transaction's timestamp is older than a previous transaction of the client.
+    OutOfOrder,
+    /// The transaction is an exact copy of one already processed, as sent again when a
+    /// batch is retried, and was ignored.
+    ///
+    /// Only used by engines with idempotency enabled.
+    Replayed,
+    /// A deposit or withdrawal reused the ID of an already processed transaction with a
+    /// different type or amount. With idempotency enabled, this also covers IDs of other
+    /// clients' transactions and of rejected ones.
+    ///
+    /// This is an anomaly of [`Severity::High`].
+    ConflictingTransaction,
+}
+
+impl Rejection {
+    /// Every reason, with each [`Limit`] that can be exceeded.
+    pub const ALL: [Self; 17] = [
+        Self::AccountLocked,
+        Self::DuplicateTransaction,
+        Self::InsufficientFunds,
+        Self::UnknownTransaction,
+        Self::NotDisputable,
+        Self::AlreadyDisputed,
+        Self::NotDisputed,
+        Self::InvalidDisputeTransition,
+        Self::InvalidAmount,
+        Self::DisputeWindowExpired,
+        Self::LimitExceeded(Limit::Withdrawal),
+        Self::LimitExceeded(Limit::DailyWithdrawal),
+        Self::LimitExceeded(Limit::Velocity),
+        Self::LimitExceeded(Limit::Held),
+        Self::OutOfOrder,
+        Self::Replayed,
+        Self::ConflictingTransaction,
+    ];
+
+    /// The position of the reason in [`Rejection::ALL`].
+    pub(crate) const fn index(self) -> usize {
+        match self {
+            Self::AccountLocked => 0,
+            Self::DuplicateTransaction => 1,
+            Self::InsufficientFunds => 2,
+            Self::UnknownTransaction => 3,
+            Self::NotDisputable => 4,
+            Self::AlreadyDisputed => 5,
+            Self::NotDisputed => 6,
+            Self::InvalidDisputeTransition => 7,
+            Self::InvalidAmount => 8,
+            Self::DisputeWindowExpired => 9,
+            Self::LimitExceeded(limit) => 10 + limit as usize,
+            Self::OutOfOrder => 14,
+            Self::Replayed => 15,
+            Self::ConflictingTransaction => 16,
+        }
+    }
+
+    /// A stable snake_case identifier of the reason, e.g. for metric labels.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::AccountLocked => "account_locked",
+            Self::DuplicateTransaction => "duplicate_transaction",
+            Self::InsufficientFunds => "insufficient_funds",
+            Self::UnknownTransaction => "unknown_transaction",
+            Self::NotDisputable => "not_disputable",
+            Self::AlreadyDisputed => "already_disputed",
+            Self::NotDisputed => "not_disputed",
+            Self::InvalidDisputeTransition => "invalid_dispute_transition",
+            Self::InvalidAmount => "invalid_amount",
+            Self::DisputeWindowExpired => "dispute_window_expired",
+            Self::LimitExceeded(Limit::Withdrawal) => "withdrawal_limit_exceeded",
+            Self::LimitExceeded(Limit::DailyWithdrawal) => "daily_withdrawal_limit_exceeded",
+            Self::LimitExceeded(Limit::Velocity) => "velocity_limit_exceeded",
+            Self::LimitExceeded(Limit::Held) => "held_limit_exceeded",
+            Self::OutOfOrder => "out_of_order",
+            Self::Replayed => "replayed",
+            Self::ConflictingTransaction => "conflicting_transaction",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            Self::ConflictingTransaction => Severity::High,
+            _ => Severity::Normal,
+        }
+    }
+}
+
+/// How much a [`Rejection`] calls for attention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Expected in normal operation, e.g. insufficient funds.
+    Normal,
+    /// An anomaly that usually signals a bug on the sender's side, e.g. a transaction ID
+    /// reused for a different amount.
+    High,
+}
+
+/// A per-client limit that can cause a [`Rejection::LimitExceeded`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Limit {
+    /// Maximum amount of a single withdrawal.
+    Withdrawal,
+    /// Maximum sum of withdrawals in a single UTC day.
+    DailyWithdrawal,
+    /// Maximum number of transactions in a time window.
+    Velocity,
+    /// Maximum amount of held funds.
+    Held,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AccountLocked => f.write_str("account is locked"),
+            Self::DuplicateTransaction => f.write_str("duplicate transaction id"),
+            Self::InsufficientFunds => f.write_str("insufficient available funds"),
+            Self::UnknownTransaction => f.write_str("unknown transaction"),
+            Self::NotDisputable => f.write_str("transaction cannot be disputed"),
+            Self::AlreadyDisputed => f.write_str("transaction is already disputed"),
+            Self::NotDisputed => f.write_str("transaction is not disputed"),
+            Self::InvalidDisputeTransition => f.write_str("invalid dispute transition"),
+            Self::InvalidAmount => f.write_str("invalid amount"),
+            Self::DisputeWindowExpired => f.write_str("dispute window has expired"),
+            Self::LimitExceeded(limit) => write!(f, "{limit} limit exceeded"),
+            Self::OutOfOrder => f.write_str("transaction is out of order"),
+            Self::Replayed => f.write_str("transaction was already processed"),
+            Self::ConflictingTransaction => {
+                f.write_str("transaction id was already used with different contents")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Withdrawal => "withdrawal",
+            Self::DailyWithdrawal => "daily withdrawal",
+            Self::Velocity => "velocity",
+            Self::Held => "held amount",
+        })
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+impl From<AccountError> for Rejection {
+    fn from(err: AccountError) -> Self {
+        match err {
+            AccountError::Locked => Self::AccountLocked,
+            AccountError::InsufficientFunds => Self::InsufficientFunds,
+            AccountError::InsufficientHeld | AccountError::InvalidAmount => Self::InvalidAmount,
+            // Only representment reverses chargebacks, and only once disputed again.
+            AccountError::NotLocked => Self::InvalidDisputeTransition,
+        }
+    }
+}
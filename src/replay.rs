@@ -0,0 +1,90 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Amount, DisputeState, Rejection, Transaction, TransactionKind, stats::table_size};
+
+/// The contents identifying a row, regardless of when it was sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct RowKey {
+    kind: TransactionKind,
+    client: u16,
+    tx: u32,
+    amount: Option<Amount>,
+}
+
+impl RowKey {
+    fn new(tx: &Transaction) -> Self {
+        Self {
+            kind: tx.kind(),
+            client: tx.client,
+            tx: tx.id,
+            amount: tx.amount(),
+        }
+    }
+
+    fn is_dispute_step(&self) -> bool {
+        !matches!(
+            self.kind,
+            TransactionKind::Deposit | TransactionKind::Withdrawal
+        )
+    }
+}
+
+/// Recognizes transactions sent again, e.g. when a partner retries a batch.
+#[derive(Debug, Default)]
+pub(crate) struct ReplayGuard {
+    /// Every deposit and withdrawal processed so far, accepted or not.
+    rows: HashSet<RowKey>,
+    /// The first row seen with each deposit or withdrawal ID.
+    ids: HashMap<u32, RowKey>,
+    /// Every dispute, resolve and chargeback processed so far, with the state they left
+    /// the dispute in.
+    ///
+    /// The same row can legitimately come again later in the dispute lifecycle, e.g. a
+    /// dispute reopening a resolved one, so it is only a copy while the dispute is
+    /// still in that state.
+    steps: HashSet<(RowKey, Option<DisputeState>)>,
+}
+
+impl ReplayGuard {
+    pub(crate) fn heap_size(&self) -> usize {
+        table_size::<RowKey>(self.rows.capacity())
+            + table_size::<(u32, RowKey)>(self.ids.capacity())
+            + table_size::<(RowKey, Option<DisputeState>)>(self.steps.capacity())
+    }
+
+    /// Check that the transaction is neither a copy of a row already processed nor
+    /// conflicts with one, given the current state of the dispute it refers to.
+    ///
+    /// Deposits and withdrawals are remembered right away, while disputes, resolves
+    /// and chargebacks must be passed to [`ReplayGuard::record`] once processed.
+    pub(crate) fn check(
+        &mut self,
+        tx: &Transaction,
+        dispute: Option<DisputeState>,
+    ) -> Result<(), Rejection> {
+        let key = RowKey::new(tx);
+        if key.is_dispute_step() {
+            return if self.steps.contains(&(key, dispute)) {
+                Err(Rejection::Replayed)
+            } else {
+                Ok(())
+            };
+        }
+
+        if !self.rows.insert(key) {
+            return Err(Rejection::Replayed);
+        }
+        if *self.ids.entry(key.tx).or_insert(key) != key {
+            return Err(Rejection::ConflictingTransaction);
+        }
+        Ok(())
+    }
+
+    /// Remember a processed transaction with the state it left its dispute in.
+    pub(crate) fn record(&mut self, tx: &Transaction, dispute: Option<DisputeState>) {
+        let key = RowKey::new(tx);
+        if key.is_dispute_step() {
+            self.steps.insert((key, dispute));
+        }
+    }
+}
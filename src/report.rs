@@ -0,0 +1,359 @@
+use std::{fmt, io, str::FromStr};
+
+use crate::{Account, Amount, Engine};
+
+/// A column of an [`AccountReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Client,
+    Available,
+    Held,
+    Total,
+    Locked,
+    /// Funds owed by the client after disputes held more than it had available.
+    Deficit,
+    /// Number of transactions processed for the client, including rejected ones.
+    Transactions,
+}
+
+impl Column {
+    /// The columns of the CLI's default output.
+    pub const DEFAULT: [Self; 5] = [
+        Self::Client,
+        Self::Available,
+        Self::Held,
+        Self::Total,
+        Self::Locked,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Client => "client",
+            Self::Available => "available",
+            Self::Held => "held",
+            Self::Total => "total",
+            Self::Locked => "locked",
+            Self::Deficit => "deficit",
+            Self::Transactions => "transactions",
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = ParseReportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Self::Client,
+            Self::Available,
+            Self::Held,
+            Self::Total,
+            Self::Locked,
+            Self::Deficit,
+            Self::Transactions,
+        ]
+        .into_iter()
+        .find(|column| column.name() == s)
+        .ok_or_else(|| ParseReportError::new("column", s))
+    }
+}
+
+/// The order of the rows of an [`AccountReport`].
+///
+/// Rows with equal amounts are ordered by client ID.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// By client ID, ascending.
+    #[default]
+    Client,
+    /// By total funds, largest first.
+    Total,
+    /// By available funds, largest first.
+    Available,
+}
+
+impl FromStr for SortKey {
+    type Err = ParseReportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(Self::Client),
+            "total" => Ok(Self::Total),
+            "available" => Ok(Self::Available),
+            _ => Err(ParseReportError::new("sort key", s)),
+        }
+    }
+}
+
+/// Which accounts an [`AccountReport`] includes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountFilter {
+    Locked,
+    Unlocked,
+    /// Accounts with non-zero total funds.
+    NonZero,
+    /// Accounts with funds held by disputes.
+    HasHeld,
+}
+
+impl AccountFilter {
+    fn matches(self, account: &Account) -> bool {
+        match self {
+            Self::Locked => account.is_locked(),
+            Self::Unlocked => !account.is_locked(),
+            Self::NonZero => !account.total_funds().is_zero(),
+            Self::HasHeld => !account.held_funds().is_zero(),
+        }
+    }
+}
+
+impl FromStr for AccountFilter {
+    type Err = ParseReportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "locked" => Ok(Self::Locked),
+            "unlocked" => Ok(Self::Unlocked),
+            "nonzero" => Ok(Self::NonZero),
+            "has-held" => Ok(Self::HasHeld),
+            _ => Err(ParseReportError::new("filter", s)),
+        }
+    }
+}
+
+/// Error parsing a [`Column`], [`SortKey`] or [`AccountFilter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseReportError {
+    kind: &'static str,
+    value: String,
+}
+
+impl ParseReportError {
+    fn new(kind: &'static str, value: &str) -> Self {
+        Self {
+            kind,
+            value: value.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown {} {:?}", self.kind, self.value)
+    }
+}
+
+impl std::error::Error for ParseReportError {}
+
+/// A table of accounts, built over [`Engine::accounts`].
+///
+/// By default includes every account, ordered by client ID, with the
+/// [`Column::DEFAULT`] columns and amounts without trailing zeros.
+#[derive(Clone)]
+pub struct AccountReport<'a> {
+    engine: &'a Engine,
+    sort: SortKey,
+    filters: Vec<AccountFilter>,
+    columns: Vec<Column>,
+    decimals: Option<u8>,
+}
+
+/// A row of an [`AccountReport`].
+#[derive(Debug, Clone, Copy)]
+pub struct ReportRow<'a> {
+    pub client: u16,
+    pub account: &'a Account,
+}
+
+impl<'a> AccountReport<'a> {
+    /// The most decimal places amounts can be shown with.
+    pub const MAX_DECIMALS: u8 = 28;
+
+    pub fn new(engine: &'a Engine) -> Self {
+        Self {
+            engine,
+            sort: SortKey::default(),
+            filters: vec![],
+            columns: Column::DEFAULT.to_vec(),
+            decimals: None,
+        }
+    }
+
+    pub fn sort_by(mut self, sort: SortKey) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Only include accounts matching the filter, on top of any previous ones.
+    pub fn filter(mut self, filter: AccountFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn columns(mut self, columns: impl IntoIterator<Item = Column>) -> Self {
+        self.columns = columns.into_iter().collect();
+        self
+    }
+
+    /// Show amounts with exactly `decimals` decimal places, rounding half up.
+    ///
+    /// Capped at [`AccountReport::MAX_DECIMALS`].
+    pub fn decimals(mut self, decimals: u8) -> Self {
+        self.decimals = Some(decimals.min(Self::MAX_DECIMALS));
+        self
+    }
+
+    /// The accounts included in the report, in order.
+    pub fn rows(&self) -> Vec<ReportRow<'a>> {
+        let mut rows: Vec<_> = self
+            .engine
+            .accounts()
+            .filter(|(_, account)| self.filters.iter().all(|filter| filter.matches(account)))
+            .map(|(client, account)| ReportRow { client, account })
+            .collect();
+
+        // Accounts are iterated by client ID, so a stable sort keeps ties in that order.
+        match self.sort {
+            SortKey::Client => {}
+            SortKey::Total => {
+                rows.sort_by(|a, b| b.account.total_funds().cmp(&a.account.total_funds()))
+            }
+            SortKey::Available => rows.sort_by(|a, b| {
+                b.account
+                    .available_funds()
+                    .cmp(&a.account.available_funds())
+            }),
+        }
+
+        rows
+    }
+
+    /// The formatted values of a row, one per column.
+    pub fn values(&self, row: &ReportRow<'_>) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|column| match column {
+                Column::Client => row.client.to_string(),
+                Column::Available => self.amount(row.account.available_funds()),
+                Column::Held => self.amount(row.account.held_funds()),
+                Column::Total => self.amount(row.account.total_funds()),
+                Column::Locked => row.account.is_locked().to_string(),
+                Column::Deficit => self.amount(row.account.deficit()),
+                Column::Transactions => self
+                    .engine
+                    .client_transactions(row.client, 0, usize::MAX)
+                    .len()
+                    .to_string(),
+            })
+            .collect()
+    }
+
+    fn amount(&self, amount: Amount) -> String {
+        match self.decimals {
+            Some(decimals) => amount.rescale(decimals.into()).to_string(),
+            None => amount.reduce().to_string(),
+        }
+    }
+
+    /// Write the report as CSV, with a header row.
+    pub fn write_csv(&self, writer: impl io::Write) -> csv::Result<()> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(self.columns.iter().map(|column| column.name()))?;
+
+        for row in self.rows() {
+            wtr.write_record(self.values(&row))?;
+        }
+
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Transaction, TxPayload};
+
+    fn engine() -> Engine {
+        let mut engine = Engine::default();
+        let tx = |id, client, payload| Transaction {
+            id,
+            client,
+            payload,
+            timestamp: None,
+        };
+        let deposit = |amount: &str| TxPayload::Deposit {
+            amount: amount.parse().unwrap(),
+        };
+        for tx in [
+            tx(1, 1, deposit("10")),
+            tx(2, 2, deposit("30.5")),
+            tx(3, 3, deposit("20")),
+            tx(4, 4, deposit("5")),
+            tx(3, 3, TxPayload::Dispute { amount: None }),
+            tx(4, 4, TxPayload::Dispute { amount: None }),
+            tx(4, 4, TxPayload::Chargeback { amount: None }),
+            tx(5, 4, deposit("1")),
+        ] {
+            let _ = engine.process_transaction(tx);
+        }
+        engine
+    }
+
+    fn csv(report: &AccountReport<'_>) -> String {
+        let mut out = vec![];
+        report.write_csv(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_default_report() {
+        let engine = engine();
+        assert_eq!(
+            csv(&AccountReport::new(&engine)),
+            "client,available,held,total,locked\n\
+             1,10,0,10,false\n\
+             2,30.5,0,30.5,false\n\
+             3,0,20,20,false\n\
+             4,0,0,0,true\n"
+        );
+    }
+
+    #[test]
+    fn test_sorted_filtered_report() {
+        let engine = engine();
+        let report = AccountReport::new(&engine)
+            .sort_by(SortKey::Total)
+            .filter(AccountFilter::Unlocked)
+            .filter(AccountFilter::NonZero)
+            .columns([Column::Client, Column::Total, Column::Transactions])
+            .decimals(2);
+        assert_eq!(
+            csv(&report),
+            "client,total,transactions\n\
+             2,30.50,1\n\
+             3,20.00,2\n\
+             1,10.00,1\n"
+        );
+
+        let report = AccountReport::new(&engine)
+            .sort_by(SortKey::Available)
+            .filter(AccountFilter::HasHeld);
+        let clients: Vec<_> = report.rows().iter().map(|row| row.client).collect();
+        assert_eq!(clients, [3]);
+
+        let locked = AccountReport::new(&engine).filter(AccountFilter::Locked);
+        assert_eq!(locked.rows().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_report_options() {
+        assert_eq!("has-held".parse(), Ok(AccountFilter::HasHeld));
+        assert_eq!("transactions".parse(), Ok(Column::Transactions));
+        assert_eq!("available".parse(), Ok(SortKey::Available));
+        assert_eq!(
+            "balance".parse::<SortKey>().unwrap_err().to_string(),
+            "unknown sort key \"balance\""
+        );
+    }
+}
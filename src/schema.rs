@@ -0,0 +1,371 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use crate::{ConfigError, Transaction, TransactionKind};
+
+/// A field of a [`Transaction`] in a CSV file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    Type,
+    Client,
+    Tx,
+    Amount,
+    Timestamp,
+}
+
+impl Field {
+    pub const ALL: [Self; 5] = [
+        Self::Type,
+        Self::Client,
+        Self::Tx,
+        Self::Amount,
+        Self::Timestamp,
+    ];
+
+    /// The name of the field's column in the canonical format.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Type => "type",
+            Self::Client => "client",
+            Self::Tx => "tx",
+            Self::Amount => "amount",
+            Self::Timestamp => "timestamp",
+        }
+    }
+
+    /// Whether rows can leave the field out, so that files can omit its column.
+    fn is_optional(self) -> bool {
+        matches!(self, Self::Amount | Self::Timestamp)
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Where a [`Field`] is in the rows of a CSV file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    /// The column with this header.
+    Name(String),
+    /// The column at this position, starting from 0.
+    Position(usize),
+}
+
+/// The layout of CSV files of transactions that don't use the canonical format.
+///
+/// It can be loaded from a TOML file with the following layout:
+///
+/// ```toml
+/// delimiter = ";"
+///
+/// # The columns of the fields, by header or by position without headers. Fields not
+/// # listed use their canonical column.
+/// [columns]
+/// type = "txn_type"
+/// client = "client_id"
+/// tx = "transaction_id"
+/// amount = "value"
+///
+/// # Other names for the transaction types, ignoring case like the canonical ones.
+/// [aliases]
+/// credit = "deposit"
+/// debit = "withdrawal"
+/// ```
+///
+/// With `headers = false`, every row is a transaction and the columns default to the
+/// canonical order: type, client, tx, amount and timestamp.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    delimiter: u8,
+    headers: bool,
+    columns: HashMap<Field, ColumnRef>,
+    /// Lowercase aliases.
+    aliases: HashMap<String, TransactionKind>,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            headers: true,
+            columns: HashMap::new(),
+            aliases: HashMap::new(),
+        }
+    }
+}
+
+impl Schema {
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Read files without a header row, whose columns are all given by position.
+    pub fn without_headers(mut self) -> Self {
+        self.headers = false;
+        self
+    }
+
+    pub fn with_column(mut self, field: Field, column: ColumnRef) -> Self {
+        self.columns.insert(field, column);
+        self
+    }
+
+    /// Accept another name for a transaction type, ignoring ASCII case.
+    pub fn with_alias(mut self, alias: &str, kind: TransactionKind) -> Self {
+        self.aliases.insert(alias.to_ascii_lowercase(), kind);
+        self
+    }
+
+    /// Parse the schema from a TOML string.
+    pub fn from_toml_str(s: &str) -> Result<Self, ConfigError> {
+        fn deserialize_delimiter<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let delimiter = <char as serde::Deserialize>::deserialize(deserializer)?;
+            if !delimiter.is_ascii() {
+                let msg = format!("delimiter {delimiter:?} is not ASCII");
+                return Err(serde::de::Error::custom(msg));
+            }
+            Ok(Some(delimiter as u8))
+        }
+
+        #[derive(serde::Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Raw {
+            #[serde(default, deserialize_with = "deserialize_delimiter")]
+            delimiter: Option<u8>,
+            headers: Option<bool>,
+            #[serde(default)]
+            columns: HashMap<Field, ColumnRef>,
+            #[serde(default)]
+            aliases: HashMap<String, TransactionKind>,
+        }
+
+        let raw: Raw = toml::from_str(s).map_err(ConfigError::Parse)?;
+
+        let mut schema = Self::default();
+        if let Some(delimiter) = raw.delimiter {
+            schema = schema.with_delimiter(delimiter);
+        }
+        if raw.headers == Some(false) {
+            schema = schema.without_headers();
+        }
+        for (field, column) in raw.columns {
+            schema = schema.with_column(field, column);
+        }
+        for (alias, kind) in raw.aliases {
+            schema = schema.with_alias(&alias, kind);
+        }
+        Ok(schema)
+    }
+
+    /// Read and parse the schema from a TOML file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::from_toml_str(&contents)
+    }
+
+    /// A CSV reader builder for files with this delimiter and header row.
+    pub fn reader_builder(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder.delimiter(self.delimiter).has_headers(self.headers);
+        builder
+    }
+
+    /// Resolve the columns for a file with the given header row, if it has one.
+    pub fn mapping(
+        &self,
+        headers: Option<&csv::StringRecord>,
+    ) -> Result<SchemaMapping, SchemaError> {
+        let mut columns = [None; Field::ALL.len()];
+        for field in Field::ALL {
+            let column = match (self.columns.get(&field), headers) {
+                (Some(ColumnRef::Position(position)), _) => Some(*position),
+                (Some(ColumnRef::Name(name)), Some(headers)) => position(headers, name),
+                (None, Some(headers)) => position(headers, field.name()),
+                (Some(ColumnRef::Name(name)), None) => {
+                    return Err(SchemaError::NoHeaders(name.clone()));
+                }
+                (None, None) => Some(field.index()),
+            };
+            if column.is_none() && !field.is_optional() {
+                let name = match self.columns.get(&field) {
+                    Some(ColumnRef::Name(name)) => name.clone(),
+                    _ => field.name().to_string(),
+                };
+                return Err(SchemaError::MissingColumn(name));
+            }
+            columns[field.index()] = column;
+        }
+
+        // Files in the canonical format are deserialized as they are.
+        let canonical = headers.filter(|_| self.columns.is_empty() && self.aliases.is_empty());
+        Ok(SchemaMapping {
+            columns,
+            headers: canonical
+                .cloned()
+                .unwrap_or_else(|| Field::ALL.iter().map(|field| field.name()).collect()),
+            canonical: canonical.is_some(),
+            aliases: self.aliases.clone(),
+        })
+    }
+}
+
+fn position(headers: &csv::StringRecord, name: &str) -> Option<usize> {
+    headers.iter().position(|header| header == name)
+}
+
+/// The columns of a [`Schema`] resolved for a file, to read its rows as transactions.
+#[derive(Debug, Clone)]
+pub struct SchemaMapping {
+    columns: [Option<usize>; Field::ALL.len()],
+    headers: csv::StringRecord,
+    canonical: bool,
+    aliases: HashMap<String, TransactionKind>,
+}
+
+impl SchemaMapping {
+    /// Deserialize a row of the file.
+    pub fn transaction(&self, record: &csv::StringRecord) -> csv::Result<Transaction> {
+        if self.canonical {
+            return record.deserialize(Some(&self.headers));
+        }
+
+        let value = |field: Field| {
+            self.columns[field.index()]
+                .and_then(|column| record.get(column))
+                .unwrap_or_default()
+        };
+        let typ = value(Field::Type);
+        let typ = match self.aliases.get(&typ.to_ascii_lowercase()) {
+            Some(kind) => kind.name(),
+            None => typ,
+        };
+
+        let mut row = csv::StringRecord::with_capacity(record.as_slice().len(), Field::ALL.len());
+        row.push_field(typ);
+        for field in &Field::ALL[1..] {
+            row.push_field(value(*field));
+        }
+        row.set_position(record.position().cloned());
+        row.deserialize(Some(&self.headers))
+    }
+}
+
+/// Error resolving the columns of a [`Schema`] for a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    /// A required column isn't in the header row.
+    MissingColumn(String),
+    /// A column is given by name, but the file has no header row.
+    NoHeaders(String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingColumn(name) => write!(f, "missing column {name:?}"),
+            Self::NoHeaders(name) => {
+                write!(
+                    f,
+                    "column {name:?} is given by name, but there are no headers"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(schema: &Schema, input: &str) -> Vec<Transaction> {
+        let mut reader = schema.reader_builder().from_reader(input.as_bytes());
+        let headers = schema.headers.then(|| reader.headers().unwrap().clone());
+        let mapping = schema.mapping(headers.as_ref()).unwrap();
+        reader
+            .records()
+            .map(|record| mapping.transaction(&record.unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_partner_layout() {
+        let schema = Schema::from_toml_str(
+            r#"
+            delimiter = ";"
+
+            [columns]
+            type = "txn_type"
+            client = "client_id"
+            tx = "transaction_id"
+            amount = "value"
+
+            [aliases]
+            Credit = "deposit"
+            "#,
+        )
+        .unwrap();
+
+        let txs = read(
+            &schema,
+            "transaction_id;client_id;value;txn_type\n1;2;10.5;DEPOSIT\n2;2;1;credit\n1;2;;Dispute\n",
+        );
+        let kinds: Vec<_> = txs.iter().map(|tx| tx.kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                TransactionKind::Deposit,
+                TransactionKind::Deposit,
+                TransactionKind::Dispute
+            ]
+        );
+        assert_eq!((txs[0].client(), txs[0].id()), (2, 1));
+        assert_eq!(txs[0].amount(), Some("10.5".parse().unwrap()));
+        assert_eq!(txs[2].amount(), None);
+    }
+
+    #[test]
+    fn test_headerless() {
+        let schema = Schema::default().without_headers();
+        let txs = read(&schema, "deposit,1,1,10\nwithdrawal,1,2,5\n");
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[1].kind(), TransactionKind::Withdrawal);
+
+        let schema = Schema::default()
+            .without_headers()
+            .with_column(Field::Type, ColumnRef::Position(3))
+            .with_column(Field::Amount, ColumnRef::Position(0));
+        let txs = read(&schema, "10,1,1,deposit\n");
+        assert_eq!(txs[0].kind(), TransactionKind::Deposit);
+        assert_eq!(txs[0].amount(), Some("10".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_mapping_errors() {
+        let headers = csv::StringRecord::from(vec!["type", "client", "amount"]);
+        assert_eq!(
+            Schema::default().mapping(Some(&headers)).unwrap_err(),
+            SchemaError::MissingColumn("tx".to_string())
+        );
+
+        let schema = Schema::default()
+            .without_headers()
+            .with_column(Field::Tx, ColumnRef::Name("id".to_string()));
+        assert_eq!(
+            schema.mapping(None).unwrap_err(),
+            SchemaError::NoHeaders("id".to_string())
+        );
+
+        assert!(matches!(
+            Schema::from_toml_str("delimiter = \"é\""),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{Amount, Timestamp};
+
+/// A change to a client's balances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Posting {
+    /// When the change happened.
+    pub timestamp: Timestamp,
+    /// The deposit or withdrawal the change is about.
+    pub tx: u32,
+    pub kind: PostingKind,
+    /// The amount moved, always positive.
+    pub amount: Amount,
+}
+
+/// How a [`Posting`] moves funds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PostingKind {
+    /// Funds deposited into available.
+    Deposit,
+    /// Funds withdrawn from available.
+    Withdrawal,
+    /// Funds moved from available to held by a dispute.
+    Hold,
+    /// Held funds moved back to available by a resolve.
+    Release,
+    /// Held funds removed by a chargeback.
+    Chargeback,
+    /// Charged back funds credited to available again after representment.
+    ChargebackReversal,
+}
+
+impl PostingKind {
+    pub const ALL: [Self; 6] = [
+        Self::Deposit,
+        Self::Withdrawal,
+        Self::Hold,
+        Self::Release,
+        Self::Chargeback,
+        Self::ChargebackReversal,
+    ];
+
+    /// The name of the kind in statements.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Deposit => "deposit",
+            Self::Withdrawal => "withdrawal",
+            Self::Hold => "hold",
+            Self::Release => "release",
+            Self::Chargeback => "chargeback",
+            Self::ChargebackReversal => "chargeback_reversal",
+        }
+    }
+
+    /// The changes to the available and held funds when moving `amount`.
+    fn deltas(self, amount: Amount) -> (Amount, Amount) {
+        match self {
+            Self::Deposit | Self::ChargebackReversal => (amount, Amount::ZERO),
+            Self::Withdrawal => (-amount, Amount::ZERO),
+            Self::Hold => (-amount, amount),
+            Self::Release => (amount, -amount),
+            Self::Chargeback => (Amount::ZERO, -amount),
+        }
+    }
+}
+
+/// A client's balances at some point in time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Balances {
+    pub available: Amount,
+    pub held: Amount,
+}
+
+impl Balances {
+    pub fn total(&self) -> Amount {
+        self.available + self.held
+    }
+
+    fn apply(self, posting: &Posting) -> Self {
+        let (available, held) = posting.kind.deltas(posting.amount);
+        Self {
+            available: self.available + available,
+            held: self.held + held,
+        }
+    }
+}
+
+/// A posting and the client's balances right after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementLine {
+    pub posting: Posting,
+    pub balances: Balances,
+}
+
+/// Totals of dispute-related postings in a statement's period.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DisputeSummary {
+    /// Number of disputes opened or extended.
+    pub disputes: usize,
+    /// Funds held by disputes.
+    pub disputed: Amount,
+    /// Funds released by resolves.
+    pub resolved: Amount,
+    /// Funds removed by chargebacks.
+    pub charged_back: Amount,
+    /// Charged back funds credited again after representment.
+    pub reversed: Amount,
+}
+
+/// The postings of a client in a period, with running balances.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub client: u16,
+    /// Start of the period, inclusive.
+    pub from: Timestamp,
+    /// End of the period, exclusive.
+    pub to: Timestamp,
+    /// Balances before the first posting of the period.
+    pub opening: Balances,
+    /// Postings of the period, in processing order.
+    pub lines: Vec<StatementLine>,
+    /// Balances after the last posting of the period.
+    pub closing: Balances,
+    pub disputes: DisputeSummary,
+}
+
+impl Statement {
+    /// Build the statement of `client` for `[from, to)` out of all its postings.
+    pub(crate) fn new(client: u16, from: Timestamp, to: Timestamp, postings: &[Posting]) -> Self {
+        let opening = postings
+            .iter()
+            .filter(|posting| posting.timestamp < from)
+            .fold(Balances::default(), Balances::apply);
+
+        let mut balances = opening;
+        let mut disputes = DisputeSummary::default();
+        let lines = postings
+            .iter()
+            .filter(|posting| (from..to).contains(&posting.timestamp))
+            .map(|&posting| {
+                balances = balances.apply(&posting);
+                match posting.kind {
+                    PostingKind::Deposit | PostingKind::Withdrawal => {}
+                    PostingKind::Hold => {
+                        disputes.disputes += 1;
+                        disputes.disputed += posting.amount;
+                    }
+                    PostingKind::Release => disputes.resolved += posting.amount,
+                    PostingKind::Chargeback => disputes.charged_back += posting.amount,
+                    PostingKind::ChargebackReversal => disputes.reversed += posting.amount,
+                }
+                StatementLine { posting, balances }
+            })
+            .collect();
+
+        Self {
+            client,
+            from,
+            to,
+            opening,
+            lines,
+            closing: balances,
+            disputes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statement_balances() {
+        let posting = |millis, tx, kind, amount: i32| Posting {
+            timestamp: Timestamp::from_millis(millis),
+            tx,
+            kind,
+            amount: Amount::from(amount),
+        };
+        let postings = [
+            posting(0, 1, PostingKind::Deposit, 100),
+            posting(10, 2, PostingKind::Withdrawal, 30),
+            posting(20, 1, PostingKind::Hold, 50),
+            posting(30, 1, PostingKind::Chargeback, 20),
+            posting(40, 1, PostingKind::Release, 30),
+        ];
+
+        let statement = Statement::new(
+            1,
+            Timestamp::from_millis(10),
+            Timestamp::from_millis(40),
+            &postings,
+        );
+
+        assert_eq!(statement.opening.available, Amount::from(100));
+        assert_eq!(statement.lines.len(), 3);
+        assert_eq!(statement.lines[1].balances.available, Amount::from(20));
+        assert_eq!(statement.lines[1].balances.held, Amount::from(50));
+        assert_eq!(statement.closing.total(), Amount::from(50));
+        assert_eq!(statement.disputes.disputes, 1);
+        assert_eq!(statement.disputes.disputed, Amount::from(50));
+        assert_eq!(statement.disputes.charged_back, Amount::from(20));
+        assert_eq!(statement.disputes.resolved, Amount::ZERO);
+    }
+}
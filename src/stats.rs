@@ -0,0 +1,470 @@
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use crate::{Amount, DisputeState, Posting, PostingKind, Rejection, Severity, TransactionKind};
+
+/// Counts of each transaction type, indexed by [`TransactionKind`].
+type KindCounts = [u64; TransactionKind::ALL.len()];
+
+/// Statistics about the transactions processed by an engine, see
+/// [`crate::Engine::stats`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EngineStats {
+    accepted: KindCounts,
+    rejected: KindCounts,
+    ignored: KindCounts,
+    /// Rejected transactions, indexed by [`Rejection::index`].
+    rejections: [u64; Rejection::ALL.len()],
+    /// Transactions older than a previous one of their client, applied or not depending
+    /// on [`crate::Engine::with_strict_ordering`].
+    pub out_of_order: u64,
+    volumes: [Amount; PostingKind::ALL.len()],
+    /// Disputes opened, reopened or represented. Disputing more of a transaction
+    /// whose dispute is already open doesn't count.
+    pub disputes_opened: u64,
+    /// Disputes resolved, including on expiry and by arbitration.
+    pub disputes_resolved: u64,
+    /// Disputes charged back, including on expiry and by arbitration.
+    pub disputes_charged_back: u64,
+    pub clients: usize,
+    pub locked_accounts: usize,
+    /// Disputes pending a resolution.
+    pub open_disputes: usize,
+    /// Sum of the funds held by every account.
+    pub held: Amount,
+    /// Estimate of the memory used by the engine, in bytes.
+    ///
+    /// The engine's collections never shrink, so this is also its peak.
+    pub peak_memory: usize,
+    /// Time taken to process each transaction, in seconds, if the engine was created
+    /// with [`crate::Engine::with_timing`].
+    pub latency: Option<Histogram>,
+}
+
+impl EngineStats {
+    /// Accepted transactions of the type.
+    pub fn accepted(&self, kind: TransactionKind) -> u64 {
+        self.accepted[kind as usize]
+    }
+
+    /// Rejected transactions of the type, not counting those ignored as replays.
+    pub fn rejected(&self, kind: TransactionKind) -> u64 {
+        self.rejected[kind as usize]
+    }
+
+    /// Transactions of the type ignored as replays, see
+    /// [`crate::Engine::with_idempotency`].
+    pub fn ignored(&self, kind: TransactionKind) -> u64 {
+        self.ignored[kind as usize]
+    }
+
+    /// Transactions rejected for the reason.
+    pub fn rejections(&self, rejection: Rejection) -> u64 {
+        self.rejections[rejection.index()]
+    }
+
+    /// Sum of the postings of the kind, including those of disputes closed on expiry.
+    pub fn volume(&self, kind: PostingKind) -> Amount {
+        self.volumes[kind as usize]
+    }
+
+    /// Total number of accepted transactions.
+    pub fn total_accepted(&self) -> u64 {
+        self.accepted.iter().sum()
+    }
+
+    /// Total number of rejected transactions.
+    pub fn total_rejected(&self) -> u64 {
+        self.rejected.iter().sum()
+    }
+
+    /// Total number of transactions ignored as replays.
+    pub fn total_ignored(&self) -> u64 {
+        self.ignored.iter().sum()
+    }
+
+    /// Total number of transactions rejected with a high [`Severity`].
+    pub fn total_anomalies(&self) -> u64 {
+        Rejection::ALL
+            .into_iter()
+            .filter(|rejection| rejection.severity() == Severity::High)
+            .map(|rejection| self.rejections(rejection))
+            .sum()
+    }
+
+    pub(crate) fn record(&mut self, kind: TransactionKind, result: Result<(), Rejection>) {
+        match result {
+            Ok(()) => self.accepted[kind as usize] += 1,
+            Err(Rejection::Replayed) => self.ignored[kind as usize] += 1,
+            Err(rejection) => {
+                self.rejected[kind as usize] += 1;
+                self.rejections[rejection.index()] += 1;
+            }
+        }
+    }
+
+    pub(crate) fn record_postings(&mut self, postings: &[Posting]) {
+        for posting in postings {
+            self.volumes[posting.kind as usize] += posting.amount;
+        }
+    }
+
+    /// Count the change of a dispute's state by a transaction of the kind, or by
+    /// expiring it as that kind would.
+    pub(crate) fn record_dispute(
+        &mut self,
+        kind: TransactionKind,
+        before: Option<DisputeState>,
+        after: Option<DisputeState>,
+    ) {
+        let Some(after) = after.filter(|&after| before != Some(after)) else {
+            return;
+        };
+        match (after, kind) {
+            (DisputeState::Open | DisputeState::Reopened, _) => self.disputes_opened += 1,
+            (DisputeState::Resolved, _) | (DisputeState::Arbitrated, TransactionKind::Resolve) => {
+                self.disputes_resolved += 1
+            }
+            (DisputeState::ChargedBack, _) | (DisputeState::Arbitrated, _) => {
+                self.disputes_charged_back += 1
+            }
+        }
+    }
+
+    /// The rejection reasons seen, most frequent first.
+    fn rejections_by_count(&self) -> Vec<(Rejection, u64)> {
+        let mut rejections: Vec<_> = Rejection::ALL
+            .into_iter()
+            .map(|rejection| (rejection, self.rejections(rejection)))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        rejections.sort_by_key(|&(rejection, count)| (std::cmp::Reverse(count), rejection.code()));
+        rejections
+    }
+
+    /// Write the statistics in the Prometheus text exposition format.
+    ///
+    /// Every transaction type and posting kind is written, even if unseen, so that
+    /// series don't appear midway.
+    pub fn write_prometheus(&self, mut out: impl Write) -> io::Result<()> {
+        fn header(out: &mut impl Write, name: &str, kind: &str, help: &str) -> io::Result<()> {
+            writeln!(out, "# HELP payment_engine_{name} {help}")?;
+            writeln!(out, "# TYPE payment_engine_{name} {kind}")
+        }
+
+        header(
+            &mut out,
+            "transactions_total",
+            "counter",
+            "Transactions processed, by type and outcome.",
+        )?;
+        for kind in TransactionKind::ALL {
+            for (outcome, count) in [
+                ("accepted", self.accepted(kind)),
+                ("rejected", self.rejected(kind)),
+                ("ignored", self.ignored(kind)),
+            ] {
+                writeln!(
+                    out,
+                    "payment_engine_transactions_total{{type=\"{}\",outcome=\"{outcome}\"}} {count}",
+                    kind.name()
+                )?;
+            }
+        }
+
+        header(
+            &mut out,
+            "rejections_total",
+            "counter",
+            "Rejected transactions, by reason.",
+        )?;
+        for (rejection, count) in self.rejections_by_count() {
+            let severity = match rejection.severity() {
+                Severity::Normal => "normal",
+                Severity::High => "high",
+            };
+            writeln!(
+                out,
+                "payment_engine_rejections_total{{reason=\"{}\",severity=\"{severity}\"}} {count}",
+                rejection.code()
+            )?;
+        }
+
+        header(
+            &mut out,
+            "out_of_order_total",
+            "counter",
+            "Transactions older than a previous one of their client.",
+        )?;
+        writeln!(
+            out,
+            "payment_engine_out_of_order_total {}",
+            self.out_of_order
+        )?;
+
+        header(
+            &mut out,
+            "volume_total",
+            "counter",
+            "Sum of the amounts posted to accounts, by kind.",
+        )?;
+        for kind in PostingKind::ALL {
+            writeln!(
+                out,
+                "payment_engine_volume_total{{kind=\"{}\"}} {}",
+                kind.name(),
+                self.volume(kind).reduce()
+            )?;
+        }
+
+        header(
+            &mut out,
+            "disputes_total",
+            "counter",
+            "Disputes opened, resolved and charged back.",
+        )?;
+        for (event, count) in [
+            ("opened", self.disputes_opened),
+            ("resolved", self.disputes_resolved),
+            ("charged_back", self.disputes_charged_back),
+        ] {
+            writeln!(
+                out,
+                "payment_engine_disputes_total{{event=\"{event}\"}} {count}"
+            )?;
+        }
+
+        for (name, help, value) in [
+            ("clients", "Clients with an account.", self.clients),
+            (
+                "locked_accounts",
+                "Accounts locked by a chargeback.",
+                self.locked_accounts,
+            ),
+            (
+                "open_disputes",
+                "Disputes pending a resolution.",
+                self.open_disputes,
+            ),
+            (
+                "peak_memory_bytes",
+                "Estimate of the peak memory used by the engine.",
+                self.peak_memory,
+            ),
+        ] {
+            header(&mut out, name, "gauge", help)?;
+            writeln!(out, "payment_engine_{name} {value}")?;
+        }
+
+        header(
+            &mut out,
+            "held_funds",
+            "gauge",
+            "Sum of the funds held by every account.",
+        )?;
+        writeln!(out, "payment_engine_held_funds {}", self.held.reduce())?;
+
+        if let Some(latency) = &self.latency {
+            latency.write_prometheus(
+                &mut out,
+                "payment_engine_transaction_latency_seconds",
+                "Time taken to process each transaction.",
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for EngineStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "transactions: {} accepted, {} rejected, {} ignored",
+            self.total_accepted(),
+            self.total_rejected(),
+            self.total_ignored()
+        )?;
+        for kind in TransactionKind::ALL {
+            let (accepted, rejected, ignored) =
+                (self.accepted(kind), self.rejected(kind), self.ignored(kind));
+            if accepted + rejected + ignored > 0 {
+                writeln!(
+                    f,
+                    "- {}: {accepted} accepted, {rejected} rejected, {ignored} ignored",
+                    kind.name()
+                )?;
+            }
+        }
+
+        let rejections = self.rejections_by_count();
+        if !rejections.is_empty() {
+            writeln!(f, "rejections:")?;
+            for (rejection, count) in rejections {
+                writeln!(f, "- {rejection}: {count}")?;
+            }
+        }
+        let anomalies = self.total_anomalies();
+        if anomalies > 0 {
+            writeln!(f, "anomalies: {anomalies}")?;
+        }
+
+        if self.out_of_order > 0 {
+            writeln!(f, "out of order: {}", self.out_of_order)?;
+        }
+
+        let volumes: Vec<_> = PostingKind::ALL
+            .into_iter()
+            .map(|kind| format!("{} {}", kind.name(), self.volume(kind).reduce()))
+            .collect();
+        writeln!(f, "volumes: {}", volumes.join(", "))?;
+        writeln!(
+            f,
+            "disputes: {} opened, {} resolved, {} charged back",
+            self.disputes_opened, self.disputes_resolved, self.disputes_charged_back
+        )?;
+        writeln!(
+            f,
+            "clients: {}, locked accounts: {}",
+            self.clients, self.locked_accounts
+        )?;
+        writeln!(
+            f,
+            "open disputes: {}, held: {}",
+            self.open_disputes,
+            self.held.reduce()
+        )?;
+        if let Some(latency) = &self.latency
+            && latency.count() > 0
+        {
+            writeln!(
+                f,
+                "latency: {:.1} µs on average",
+                latency.sum() / latency.count() as f64 * 1e6
+            )?;
+        }
+        writeln!(f, "peak memory: ~{} KiB", self.peak_memory.div_ceil(1024))
+    }
+}
+
+/// A distribution of observed values, counted in buckets as exported to Prometheus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Upper bounds of the buckets, ascending, without the implicit infinite one.
+    bounds: Vec<f64>,
+    /// Observations in each bucket, not cumulative, the last one above every bound.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    /// Buckets of processing latencies, in seconds, from a microsecond to 100ms.
+    pub const LATENCY_SECS: [f64; 12] = [
+        1e-6, 2.5e-6, 5e-6, 1e-5, 2.5e-5, 5e-5, 1e-4, 2.5e-4, 5e-4, 1e-3, 1e-2, 1e-1,
+    ];
+
+    /// An empty histogram with buckets up to each of the given bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds are not ascending.
+    pub fn new(bounds: impl Into<Vec<f64>>) -> Self {
+        let bounds = bounds.into();
+        assert!(
+            bounds.is_sorted_by(|a, b| a < b),
+            "histogram bounds must be ascending"
+        );
+        Self {
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            sum: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < value);
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    /// Number of observations.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Sum of the observed values.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// The upper bound of each bucket with the number of observations up to it,
+    /// ending with the infinite bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        let bounds = self.bounds.iter().copied().chain([f64::INFINITY]);
+        bounds.zip(self.counts.iter().scan(0, |cumulative, count| {
+            *cumulative += count;
+            Some(*cumulative)
+        }))
+    }
+
+    /// Write the histogram in the Prometheus text exposition format, under the given
+    /// metric name.
+    pub fn write_prometheus(&self, mut out: impl Write, name: &str, help: &str) -> io::Result<()> {
+        writeln!(out, "# HELP {name} {help}")?;
+        writeln!(out, "# TYPE {name} histogram")?;
+        for (bound, count) in self.buckets() {
+            if bound.is_infinite() {
+                writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}")?;
+            } else {
+                writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}")?;
+            }
+        }
+        writeln!(out, "{name}_sum {}", self.sum)?;
+        writeln!(out, "{name}_count {}", self.count())
+    }
+}
+
+/// Estimate of the memory allocated by a hash table with room for `capacity` entries
+/// of type `T`.
+pub(crate) fn table_size<T>(capacity: usize) -> usize {
+    // Up to an eighth of the buckets are kept empty, each with a control byte.
+    capacity * 8 / 7 * (size_of::<T>() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejection_index() {
+        for (index, rejection) in Rejection::ALL.into_iter().enumerate() {
+            assert_eq!(rejection.index(), index, "{rejection:?}");
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new([1.0, 10.0]);
+        for value in [0.5, 1.0, 2.0, 20.0] {
+            histogram.observe(value);
+        }
+        assert_eq!(
+            histogram.buckets().collect::<Vec<_>>(),
+            [(1.0, 2), (10.0, 3), (f64::INFINITY, 4)]
+        );
+        assert_eq!(histogram.sum(), 23.5);
+
+        let mut out = vec![];
+        histogram
+            .write_prometheus(&mut out, "size", "Size.")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "# HELP size Size.\n# TYPE size histogram\n\
+             size_bucket{le=\"1\"} 2\nsize_bucket{le=\"10\"} 3\nsize_bucket{le=\"+Inf\"} 4\n\
+             size_sum 23.5\nsize_count 4\n"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+pub(crate) const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1_000;
+
+/// A point in time, stored as milliseconds since the Unix epoch (UTC).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub const UNIX_EPOCH: Self = Self(0);
+
+    pub fn from_millis(millis: i64) -> Self {
+        Self(millis)
+    }
+
+    pub fn as_millis(self) -> i64 {
+        self.0
+    }
+
+    /// The current wall-clock time.
+    pub fn now() -> Self {
+        let millis = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since) => since.as_millis() as i64,
+            Err(before) => -(before.duration().as_millis() as i64),
+        };
+
+        Self(millis)
+    }
+
+    /// The number of whole UTC days since the Unix epoch.
+    pub(crate) fn day(self) -> i64 {
+        self.0.div_euclid(MILLIS_PER_DAY)
+    }
+
+    pub(crate) fn saturating_add(self, duration: Duration) -> Self {
+        Self(self.0.saturating_add(duration.as_millis() as i64))
+    }
+
+    pub(crate) fn saturating_sub(self, duration: Duration) -> Self {
+        Self(self.0.saturating_sub(duration.as_millis() as i64))
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = ParseTimestampError;
+
+    /// Parse either an RFC 3339 date-time or an integer number of milliseconds
+    /// since the Unix epoch.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(millis) = s.parse::<i64>() {
+            return Ok(Self(millis));
+        }
+
+        s.parse::<jiff::Timestamp>()
+            .map(|ts| Self(ts.as_millisecond()))
+            .map_err(|_| ParseTimestampError(s.to_string()))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// Format as an RFC 3339 date-time, or as milliseconds if out of its range.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match jiff::Timestamp::from_millisecond(self.0) {
+            Ok(ts) => ts.fmt(f),
+            Err(_) => self.0.fmt(f),
+        }
+    }
+}
+
+/// Error parsing a [`Timestamp`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTimestampError(String);
+
+impl fmt::Display for ParseTimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid timestamp {:?}: expected RFC 3339 or epoch milliseconds",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseTimestampError {}
+
+impl<'de> serde::Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct TimestampVisitor;
+
+        impl serde::de::Visitor<'_> for TimestampVisitor {
+            type Value = Timestamp;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an RFC 3339 date-time or epoch milliseconds")
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(Timestamp(v))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                i64::try_from(v)
+                    .map(Timestamp)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(TimestampVisitor)
+    }
+}
+
+/// A source of the current time.
+///
+/// Used by the engine to timestamp transactions that don't carry one.
+pub trait Clock {
+    fn now(&self) -> Timestamp;
+}
+
+/// The system's wall clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> Timestamp,
+{
+    fn now(&self) -> Timestamp {
+        self()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!("0".parse(), Ok(Timestamp::UNIX_EPOCH));
+        assert_eq!("-1000".parse(), Ok(Timestamp::from_millis(-1_000)));
+        assert_eq!(
+            "1970-01-02T00:00:00.5Z".parse(),
+            Ok(Timestamp::from_millis(MILLIS_PER_DAY + 500))
+        );
+        assert_eq!(
+            "1970-01-01T01:00:00+01:00".parse(),
+            Ok(Timestamp::UNIX_EPOCH)
+        );
+        assert!("yesterday".parse::<Timestamp>().is_err());
+        assert!("1970-01-01".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn test_display_timestamp() {
+        let ts = Timestamp::from_millis(MILLIS_PER_DAY + 500);
+        assert_eq!(ts.to_string(), "1970-01-02T00:00:00.5Z");
+        assert_eq!(ts.to_string().parse(), Ok(ts));
+        assert_eq!(
+            Timestamp::from_millis(i64::MAX).to_string(),
+            i64::MAX.to_string()
+        );
+    }
+}
@@ -1,6 +1,6 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt, str::FromStr};
 
-use crate::Amount;
+use crate::{Amount, Timestamp};
 
 /// Represents a financial transaction in the payment engine.
 #[derive(Debug, Clone, Copy)]
@@ -15,6 +15,10 @@ pub struct Transaction {
     pub(crate) client: u16,
     /// The payload of the transaction, which varies based on the transaction type.
     pub(crate) payload: TxPayload,
+    /// When the transaction happened, if known.
+    ///
+    /// Transactions without a timestamp are timestamped by the engine's clock.
+    pub(crate) timestamp: Option<Timestamp>,
 }
 
 /// Enum representing the different types of transaction payloads.
@@ -33,24 +37,138 @@ pub(crate) enum TxPayload {
     /// A dispute transaction referencing an existing transaction ID.
     ///
     /// The disputed transaction ID is stored in the `id` field of the enclosing `Transaction`.
-    Dispute,
+    Dispute {
+        /// The disputed amount, or everything not yet disputed if not given.
+        amount: Option<Amount>,
+    },
     /// A resolve transaction referencing an existing disputed transaction ID.
     ///
     /// The resolved transaction ID is stored in the `id` field of the enclosing `Transaction`.
-    Resolve,
+    Resolve {
+        /// The resolved amount, or everything under dispute if not given.
+        amount: Option<Amount>,
+    },
     /// A chargeback transaction referencing an existing disputed transaction ID.
     ///
     /// The chargeback transaction ID is stored in the `id` field of the enclosing `Transaction`.
+    Chargeback {
+        /// The charged back amount, or everything under dispute if not given.
+        amount: Option<Amount>,
+    },
+}
+
+/// The type of a [`Transaction`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionKind {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
     Chargeback,
 }
 
+impl TransactionKind {
+    pub const ALL: [Self; 5] = [
+        Self::Deposit,
+        Self::Withdrawal,
+        Self::Dispute,
+        Self::Resolve,
+        Self::Chargeback,
+    ];
+
+    /// The name of the type in the CSV format.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Deposit => "deposit",
+            Self::Withdrawal => "withdrawal",
+            Self::Dispute => "dispute",
+            Self::Resolve => "resolve",
+            Self::Chargeback => "chargeback",
+        }
+    }
+}
+
+/// Parses the name of the type, ignoring ASCII case.
+impl FromStr for TransactionKind {
+    type Err = UnknownTransactionKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or(UnknownTransactionKind)
+    }
+}
+
+/// Error parsing an unknown [`TransactionKind`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownTransactionKind;
+
+impl fmt::Display for UnknownTransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown transaction type")
+    }
+}
+
+impl std::error::Error for UnknownTransactionKind {}
+
 impl Transaction {
+    /// The transaction ID, or the referenced one for dispute-related transactions.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn client(&self) -> u16 {
+        self.client
+    }
+
+    pub fn timestamp(&self) -> Option<Timestamp> {
+        self.timestamp
+    }
+
+    /// The same transaction happening at the given time.
+    pub fn with_timestamp(mut self, timestamp: Timestamp) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn kind(&self) -> TransactionKind {
+        match self.payload {
+            TxPayload::Deposit { .. } => TransactionKind::Deposit,
+            TxPayload::Withdrawal { .. } => TransactionKind::Withdrawal,
+            TxPayload::Dispute { .. } => TransactionKind::Dispute,
+            TxPayload::Resolve { .. } => TransactionKind::Resolve,
+            TxPayload::Chargeback { .. } => TransactionKind::Chargeback,
+        }
+    }
+
+    /// The transaction's amount, if any.
+    ///
+    /// Dispute-related transactions without an amount act on the whole amount
+    /// available to them.
+    pub fn amount(&self) -> Option<Amount> {
+        match self.payload {
+            TxPayload::Deposit { amount } | TxPayload::Withdrawal { amount } => Some(amount),
+            TxPayload::Dispute { amount }
+            | TxPayload::Resolve { amount }
+            | TxPayload::Chargeback { amount } => amount,
+        }
+    }
+
     pub(crate) fn deposited_amount(&self) -> Option<Amount> {
         match self.payload {
             TxPayload::Deposit { amount } => Some(amount),
             _ => None,
         }
     }
+
+    pub(crate) fn withdrawn_amount(&self) -> Option<Amount> {
+        match self.payload {
+            TxPayload::Withdrawal { amount } => Some(amount),
+            _ => None,
+        }
+    }
 }
 
 impl<'de> serde::Deserialize<'de> for Transaction {
@@ -107,42 +225,73 @@ impl<'de> serde::Deserialize<'de> for Transaction {
             client: u16,
             tx: u32,
             amount: Option<fastnum::D256>,
+            #[serde(default)]
+            timestamp: Option<Timestamp>,
         }
 
         let helper = Inner::deserialize(deserializer)?;
 
         let amount = helper.amount.map(|amt| amt.rescale(4));
 
-        let payload = match &*helper.typ {
-            "deposit" => TxPayload::Deposit {
+        let kind = helper.typ.parse::<TransactionKind>().map_err(|_| {
+            serde::de::Error::unknown_variant(
+                &helper.typ,
+                &["deposit", "withdrawal", "dispute", "resolve", "chargeback"],
+            )
+        })?;
+        let payload = match kind {
+            TransactionKind::Deposit => TxPayload::Deposit {
                 amount: amount.ok_or_else(|| {
                     serde::de::Error::missing_field("amount for deposit transaction")
                 })?,
             },
-            "withdrawal" => TxPayload::Withdrawal {
+            TransactionKind::Withdrawal => TxPayload::Withdrawal {
                 amount: amount.ok_or_else(|| {
                     serde::de::Error::missing_field("amount for withdrawal transaction")
                 })?,
             },
-            "dispute" => TxPayload::Dispute,
-            "resolve" => TxPayload::Resolve,
-            "chargeback" => TxPayload::Chargeback,
-            _ => {
-                return Err(serde::de::Error::unknown_variant(
-                    &helper.typ,
-                    &["deposit", "withdrawal", "dispute", "resolve", "chargeback"],
-                ));
-            }
+            TransactionKind::Dispute => TxPayload::Dispute { amount },
+            TransactionKind::Resolve => TxPayload::Resolve { amount },
+            TransactionKind::Chargeback => TxPayload::Chargeback { amount },
         };
 
         Ok(Transaction {
             id: helper.tx,
             client: helper.client,
             payload,
+            timestamp: helper.timestamp,
         })
     }
 }
 
+impl serde::Serialize for Transaction {
+    /// Serialize in the format read by [`Transaction`]'s `Deserialize` implementation,
+    /// with `type`, `client`, `tx`, `amount` and `timestamp` fields.
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        #[derive(serde::Serialize)]
+        struct Outer {
+            #[serde(rename = "type")]
+            typ: &'static str,
+            client: u16,
+            tx: u32,
+            amount: Option<String>,
+            timestamp: Option<String>,
+        }
+
+        Outer {
+            typ: self.kind().name(),
+            client: self.client,
+            tx: self.id,
+            amount: self.amount().map(|amount| amount.to_string()),
+            timestamp: self.timestamp.map(|timestamp| timestamp.to_string()),
+        }
+        .serialize(serializer)
+    }
+}
+
 #[cfg(test)]
 use proptest::prelude::*;
 
@@ -166,14 +315,15 @@ prop_compose! {
         Transaction {
             id,
             client,
-            payload: match &*payload_type {
+            payload: match payload_type {
                 "deposit" => TxPayload::Deposit { amount: Amount::from(amount.abs()).rescale(4) },
                 "withdrawal" => TxPayload::Withdrawal { amount: Amount::from(amount.abs()).rescale(4) },
-                "dispute" => TxPayload::Dispute,
-                "resolve" => TxPayload::Resolve,
-                "chargeback" => TxPayload::Chargeback,
+                "dispute" => TxPayload::Dispute { amount: None },
+                "resolve" => TxPayload::Resolve { amount: None },
+                "chargeback" => TxPayload::Chargeback { amount: None },
                 _ => unreachable!(),
-            }
+            },
+            timestamp: None,
         }
     }
 }
@@ -189,16 +339,20 @@ mod tests {
                 match tx.payload {
                     TxPayload::Deposit { .. } => "deposit",
                     TxPayload::Withdrawal { .. } => "withdrawal",
-                    TxPayload::Dispute => "dispute",
-                    TxPayload::Resolve => "resolve",
-                    TxPayload::Chargeback => "chargeback",
+                    TxPayload::Dispute { .. } => "dispute",
+                    TxPayload::Resolve { .. } => "resolve",
+                    TxPayload::Chargeback { .. } => "chargeback",
                 }.to_string(),
                 tx.client.to_string(),
                 tx.id.to_string(),
                 match tx.payload {
                     TxPayload::Deposit { amount }
                     | TxPayload::Withdrawal { amount } => amount.to_string(),
-                    _ => "".to_string(),
+                    TxPayload::Dispute { amount }
+                    | TxPayload::Resolve { amount }
+                    | TxPayload::Chargeback { amount } => {
+                        amount.map(|amount| amount.to_string()).unwrap_or_default()
+                    }
                 }
                 ]
             );
@@ -217,11 +371,95 @@ mod tests {
                 (TxPayload::Withdrawal { amount: a1 }, TxPayload::Withdrawal { amount: a2 }) => {
                     prop_assert_eq!(a1, a2);
                 }
-                (TxPayload::Dispute, TxPayload::Dispute) => {}
-                (TxPayload::Resolve, TxPayload::Resolve) => {}
-                (TxPayload::Chargeback, TxPayload::Chargeback) => {}
+                (TxPayload::Dispute { amount: a1 }, TxPayload::Dispute { amount: a2 })
+                | (TxPayload::Resolve { amount: a1 }, TxPayload::Resolve { amount: a2 })
+                | (TxPayload::Chargeback { amount: a1 }, TxPayload::Chargeback { amount: a2 }) => {
+                    prop_assert_eq!(a1, a2);
+                }
                 _ => prop_assert!(false, "Mismatched payload types"),
             }
         }
     }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let input = "\
+type,client,tx,amount,timestamp
+deposit,1,1,1.5000,2024-01-01T00:00:00.25Z
+dispute,1,1,,
+";
+        let txs: Vec<Transaction> = csv::Reader::from_reader(input.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        for tx in &txs {
+            wtr.serialize(tx).unwrap();
+        }
+        let output = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        assert_eq!(output, input);
+
+        let stamped = txs[1].with_timestamp(Timestamp::from_millis(1));
+        assert_eq!(stamped.timestamp(), Some(Timestamp::from_millis(1)));
+    }
+
+    #[test]
+    fn test_partial_dispute_amounts() {
+        let input = "\
+type,client,tx,amount
+dispute,1,1,2.5
+resolve,1,1,
+chargeback,1,1,1
+";
+        let txs: Vec<Transaction> = csv::Reader::from_reader(input.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert!(matches!(
+            txs[0].payload,
+            TxPayload::Dispute { amount: Some(amount) } if amount == Amount::from(2.5)
+        ));
+        assert!(matches!(
+            txs[1].payload,
+            TxPayload::Resolve { amount: None }
+        ));
+        assert!(matches!(
+            txs[2].payload,
+            TxPayload::Chargeback { amount: Some(amount) } if amount == Amount::from(1)
+        ));
+    }
+
+    #[test]
+    fn test_optional_timestamp_column() {
+        let input = "\
+type,client,tx,amount,timestamp
+deposit,1,1,1.0,2024-01-01T00:00:00Z
+deposit,1,2,1.0,1704067200000
+withdrawal,1,3,1.0,
+";
+        let txs: Vec<Transaction> = csv::Reader::from_reader(input.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let expected = Timestamp::from_millis(1_704_067_200_000);
+        assert_eq!(txs[0].timestamp, Some(expected));
+        assert_eq!(txs[1].timestamp, Some(expected));
+        assert_eq!(txs[2].timestamp, None);
+
+        let input = "type,client,tx,amount\ndeposit,1,1,1.0\n";
+        let txs: Vec<Transaction> = csv::Reader::from_reader(input.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(txs[0].timestamp, None);
+
+        let input = "type,client,tx,amount,timestamp\ndeposit,1,1,1.0,yesterday\n";
+        let result: Result<Vec<Transaction>, _> = csv::Reader::from_reader(input.as_bytes())
+            .deserialize()
+            .collect();
+        assert!(result.is_err());
+    }
 }
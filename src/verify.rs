@@ -0,0 +1,171 @@
+use std::fmt;
+
+use crate::{Amount, Balances, PostingKind};
+
+/// The result of checking the engine's invariants, see [`crate::Engine::verify`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Reconciliation {
+    /// Sum of all accepted deposits.
+    pub deposits: Amount,
+    /// Sum of all accepted withdrawals.
+    pub withdrawals: Amount,
+    /// Sum of all chargebacks.
+    pub charged_back: Amount,
+    /// Sum of all chargebacks credited back after representment.
+    pub reversed: Amount,
+    /// Sum of the total funds of every account.
+    pub holdings: Amount,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl Reconciliation {
+    /// The holdings expected from the flow of funds.
+    pub fn expected_holdings(&self) -> Amount {
+        self.deposits - self.withdrawals - self.charged_back + self.reversed
+    }
+
+    /// Whether no discrepancies were found.
+    pub fn is_ok(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+
+    /// Add the amount of a posting to the matching total.
+    pub(crate) fn add(&mut self, kind: PostingKind, amount: Amount) {
+        match kind {
+            PostingKind::Deposit => self.deposits += amount,
+            PostingKind::Withdrawal => self.withdrawals += amount,
+            PostingKind::Chargeback => self.charged_back += amount,
+            PostingKind::ChargebackReversal => self.reversed += amount,
+            PostingKind::Hold | PostingKind::Release => {}
+        }
+    }
+}
+
+/// A broken invariant found by [`crate::Engine::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// The client's available funds are negative, but the dispute policy doesn't allow
+    /// deficits.
+    NegativeAvailable { client: u16, available: Amount },
+    /// The client's held funds are negative.
+    NegativeHeld { client: u16, held: Amount },
+    /// The client's held funds differ from the funds held by its disputes.
+    HeldMismatch {
+        client: u16,
+        held: Amount,
+        disputes: Amount,
+    },
+    /// The client's account is locked without a chargeback, or the other way around.
+    LockMismatch { client: u16, locked: bool },
+    /// The client's balances differ from the ledger's.
+    LedgerMismatch {
+        client: u16,
+        account: Balances,
+        ledger: Balances,
+    },
+    /// The holdings differ from the flow of funds.
+    HoldingsMismatch { expected: Amount, actual: Amount },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NegativeAvailable { client, available } => {
+                write!(
+                    f,
+                    "client {client} has negative available funds {available}"
+                )
+            }
+            Self::NegativeHeld { client, held } => {
+                write!(f, "client {client} has negative held funds {held}")
+            }
+            Self::HeldMismatch {
+                client,
+                held,
+                disputes,
+            } => write!(
+                f,
+                "client {client} holds {held} but its disputes hold {disputes}"
+            ),
+            Self::LockMismatch { client, locked } => {
+                if *locked {
+                    write!(f, "client {client} is locked without a chargeback")
+                } else {
+                    write!(f, "client {client} is not locked despite a chargeback")
+                }
+            }
+            Self::LedgerMismatch {
+                client,
+                account,
+                ledger,
+            } => write!(
+                f,
+                "client {client} has {}/{} available/held but the ledger has {}/{}",
+                account.available, account.held, ledger.available, ledger.held
+            ),
+            Self::HoldingsMismatch { expected, actual } => write!(
+                f,
+                "holdings {actual} differ from deposits - withdrawals - chargebacks {expected}"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Reconciliation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "deposits: {}, withdrawals: {}, charged back: {}, reversed: {}",
+            self.deposits.reduce(),
+            self.withdrawals.reduce(),
+            self.charged_back.reduce(),
+            self.reversed.reduce()
+        )?;
+        writeln!(
+            f,
+            "expected holdings: {}, actual holdings: {}",
+            self.expected_holdings().reduce(),
+            self.holdings.reduce()
+        )?;
+
+        if self.is_ok() {
+            return writeln!(f, "no discrepancies");
+        }
+
+        writeln!(f, "{} discrepancies:", self.discrepancies.len())?;
+        for discrepancy in &self.discrepancies {
+            writeln!(f, "- {discrepancy}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut reconciliation = Reconciliation::default();
+        reconciliation.add(PostingKind::Deposit, Amount::from(100));
+        reconciliation.add(PostingKind::Hold, Amount::from(40));
+        reconciliation.add(PostingKind::Chargeback, Amount::from(40));
+        reconciliation.holdings = Amount::from(60);
+        assert_eq!(reconciliation.expected_holdings(), Amount::from(60));
+        assert!(reconciliation.is_ok());
+        assert!(reconciliation.to_string().ends_with("no discrepancies\n"));
+
+        reconciliation
+            .discrepancies
+            .push(Discrepancy::LockMismatch {
+                client: 3,
+                locked: false,
+            });
+        assert!(
+            reconciliation
+                .to_string()
+                .ends_with("- client 3 is not locked despite a chargeback\n")
+        );
+    }
+}
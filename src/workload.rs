@@ -0,0 +1,366 @@
+use std::{collections::HashMap, io};
+
+use crate::{Amount, Transaction, TxPayload};
+
+/// Deterministic generator of synthetic transactions, for benchmarks and samples.
+///
+/// The same seed and parameters always generate the same transactions, on every
+/// platform. Disputes only reference the client's own deposits, and resolves and
+/// chargebacks only currently disputed ones. A chargeback still locks the client's
+/// account, rejecting the client's later transactions: with the default mix, most
+/// clients are locked early on and about two thirds of a long workload is rejected.
+/// Mixes without chargebacks, such as [`TransactionMix::DEPOSIT_HEAVY`], are mostly
+/// accepted.
+#[derive(Debug, Clone)]
+pub struct Workload {
+    pub seed: u64,
+    /// Number of clients, whose IDs start at 1.
+    pub clients: u16,
+    pub transactions: usize,
+    pub mix: TransactionMix,
+}
+
+/// Relative weights of each transaction type in a [`Workload`].
+///
+/// Disputes, resolves and chargebacks that can't reference a suitable transaction
+/// are generated as deposits instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransactionMix {
+    pub deposit: u32,
+    pub withdrawal: u32,
+    pub dispute: u32,
+    pub resolve: u32,
+    pub chargeback: u32,
+    /// Pathological transactions the engine always rejects: duplicate deposits,
+    /// negative amounts, resolves and chargebacks of undisputed deposits, and disputes
+    /// of unknown or other clients' transactions.
+    pub invalid: u32,
+}
+
+impl TransactionMix {
+    /// Mostly deposits, with a few withdrawals and no disputes.
+    pub const DEPOSIT_HEAVY: Self = Self {
+        deposit: 90,
+        withdrawal: 10,
+        dispute: 0,
+        resolve: 0,
+        chargeback: 0,
+        invalid: 0,
+    };
+    /// Deposits disputed about as often as they are made, and resolved rather than
+    /// charged back so accounts aren't locked.
+    pub const DISPUTE_HEAVY: Self = Self {
+        deposit: 35,
+        withdrawal: 5,
+        dispute: 35,
+        resolve: 25,
+        chargeback: 0,
+        invalid: 0,
+    };
+
+    fn total(&self) -> u64 {
+        [
+            self.deposit,
+            self.withdrawal,
+            self.dispute,
+            self.resolve,
+            self.chargeback,
+            self.invalid,
+        ]
+        .iter()
+        .map(|&weight| weight as u64)
+        .sum()
+    }
+}
+
+impl Default for TransactionMix {
+    fn default() -> Self {
+        Self {
+            deposit: 40,
+            withdrawal: 35,
+            dispute: 15,
+            resolve: 6,
+            chargeback: 4,
+            invalid: 0,
+        }
+    }
+}
+
+impl Workload {
+    /// 100k transactions of 1000 clients with the default mix.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            clients: 1_000,
+            transactions: 100_000,
+            mix: TransactionMix::default(),
+        }
+    }
+
+    pub fn generate(&self) -> impl Iterator<Item = Transaction> + '_ {
+        let mut generator = Generator {
+            rng: SplitMix64(self.seed),
+            next_id: 1,
+            deposits: HashMap::new(),
+            disputed: HashMap::new(),
+        };
+
+        (0..self.transactions).map(move |_| generator.next(self))
+    }
+
+    /// Write the generated transactions as CSV, in the format read by the CLI.
+    pub fn write_csv(&self, writer: impl io::Write) -> csv::Result<()> {
+        let mut wtr = csv::Writer::from_writer(writer);
+        wtr.write_record(["type", "client", "tx", "amount"])?;
+
+        for tx in self.generate() {
+            let (typ, amount) = match tx.payload {
+                TxPayload::Deposit { amount } => ("deposit", Some(amount)),
+                TxPayload::Withdrawal { amount } => ("withdrawal", Some(amount)),
+                TxPayload::Dispute { .. } => ("dispute", None),
+                TxPayload::Resolve { .. } => ("resolve", None),
+                TxPayload::Chargeback { .. } => ("chargeback", None),
+            };
+            wtr.write_record([
+                typ,
+                &tx.client.to_string(),
+                &tx.id.to_string(),
+                &amount.map(|amount| amount.to_string()).unwrap_or_default(),
+            ])?;
+        }
+
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+struct Generator {
+    rng: SplitMix64,
+    next_id: u32,
+    /// Deposits of each client that were never disputed.
+    deposits: HashMap<u16, Vec<u32>>,
+    /// Currently disputed deposits of each client.
+    disputed: HashMap<u16, Vec<u32>>,
+}
+
+impl Generator {
+    fn next(&mut self, workload: &Workload) -> Transaction {
+        let client = 1 + self.rng.below(workload.clients.max(1) as u64) as u16;
+        let mix = &workload.mix;
+        let mut roll = self.rng.below(mix.total().max(1));
+        let mut pick = |weight: u32| {
+            let picked = roll < weight as u64;
+            roll = roll.saturating_sub(weight as u64);
+            picked
+        };
+
+        if pick(mix.deposit) {
+            self.deposit(client)
+        } else if pick(mix.withdrawal) {
+            let amount = self.amount();
+            self.new_tx(client, TxPayload::Withdrawal { amount })
+        } else if pick(mix.dispute) {
+            match take_random(&mut self.rng, self.deposits.get_mut(&client)) {
+                Some(id) => {
+                    self.disputed.entry(client).or_default().push(id);
+                    existing_tx(id, client, TxPayload::Dispute { amount: None })
+                }
+                None => self.deposit(client),
+            }
+        } else if pick(mix.resolve) {
+            self.settle(client, TxPayload::Resolve { amount: None })
+        } else if pick(mix.chargeback) {
+            self.settle(client, TxPayload::Chargeback { amount: None })
+        } else {
+            self.invalid(client, workload.clients.max(1))
+        }
+    }
+
+    /// Resolve or charge back one of the client's disputes.
+    fn settle(&mut self, client: u16, payload: TxPayload) -> Transaction {
+        match take_random(&mut self.rng, self.disputed.get_mut(&client)) {
+            Some(id) => existing_tx(id, client, payload),
+            None => self.deposit(client),
+        }
+    }
+
+    /// A transaction that is always rejected.
+    fn invalid(&mut self, client: u16, clients: u16) -> Transaction {
+        let other = 1 + self.rng.below(clients as u64) as u16;
+        let own_deposit = self.random_deposit(client);
+        let other_deposit = (other != client)
+            .then(|| self.random_deposit(other))
+            .flatten();
+        let settlement = match self.rng.below(2) {
+            0 => TxPayload::Resolve { amount: None },
+            _ => TxPayload::Chargeback { amount: None },
+        };
+
+        match (self.rng.below(5), own_deposit, other_deposit) {
+            (0, Some(id), _) => {
+                let amount = self.amount();
+                existing_tx(id, client, TxPayload::Deposit { amount })
+            }
+            (1, Some(id), _) => existing_tx(id, client, settlement),
+            (2, _, Some(id)) => existing_tx(id, client, TxPayload::Dispute { amount: None }),
+            (3, _, _) => {
+                let amount = -self.amount();
+                let payload = match self.rng.below(2) {
+                    0 => TxPayload::Deposit { amount },
+                    _ => TxPayload::Withdrawal { amount },
+                };
+                self.new_tx(client, payload)
+            }
+            // IDs this high are never generated.
+            _ => {
+                let id = u32::MAX - self.rng.below(1_000) as u32;
+                existing_tx(id, client, TxPayload::Dispute { amount: None })
+            }
+        }
+    }
+
+    /// One of the client's deposits that were never disputed.
+    fn random_deposit(&mut self, client: u16) -> Option<u32> {
+        let ids = self.deposits.get(&client).filter(|ids| !ids.is_empty())?;
+        Some(ids[self.rng.below(ids.len() as u64) as usize])
+    }
+
+    fn deposit(&mut self, client: u16) -> Transaction {
+        let amount = self.amount();
+        let tx = self.new_tx(client, TxPayload::Deposit { amount });
+        self.deposits.entry(client).or_default().push(tx.id);
+        tx
+    }
+
+    fn new_tx(&mut self, client: u16, payload: TxPayload) -> Transaction {
+        let id = self.next_id;
+        self.next_id += 1;
+        Transaction {
+            id,
+            client,
+            payload,
+            timestamp: None,
+        }
+    }
+
+    /// An amount with 4 decimal places, mostly up to 100 and otherwise up to 10000.
+    fn amount(&mut self) -> Amount {
+        let units = if self.rng.below(10) < 7 {
+            100 + self.rng.below(1_000_000)
+        } else {
+            1_000_000 + self.rng.below(99_000_000)
+        };
+        (Amount::from(units) / Amount::from(10_000)).rescale(4)
+    }
+}
+
+/// A transaction with the given ID rather than the next one.
+fn existing_tx(id: u32, client: u16, payload: TxPayload) -> Transaction {
+    Transaction {
+        id,
+        client,
+        payload,
+        timestamp: None,
+    }
+}
+
+fn take_random(rng: &mut SplitMix64, ids: Option<&mut Vec<u32>>) -> Option<u32> {
+    let ids = ids.filter(|ids| !ids.is_empty())?;
+    let index = rng.below(ids.len() as u64) as usize;
+    Some(ids.swap_remove(index))
+}
+
+/// The SplitMix64 pseudo-random number generator.
+///
+/// Not suitable for anything but reproducible test data.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`, with a negligible bias for small `n`.
+    fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    #[test]
+    fn test_workload_is_deterministic() {
+        let workload = Workload {
+            transactions: 1_000,
+            clients: 10,
+            ..Workload::new(42)
+        };
+
+        let mut first = vec![];
+        workload.write_csv(&mut first).unwrap();
+        let mut second = vec![];
+        workload.write_csv(&mut second).unwrap();
+        assert_eq!(first, second);
+
+        let mut other = vec![];
+        Workload::new(43).write_csv(&mut other).unwrap();
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_workload_is_mostly_valid() {
+        let workload = Workload {
+            transactions: 10_000,
+            clients: 50,
+            mix: TransactionMix::DISPUTE_HEAVY,
+            ..Workload::new(7)
+        };
+
+        let mut engine = Engine::default();
+        let mut accepted = 0;
+        let mut disputes = 0;
+        for tx in workload.generate() {
+            assert!((1..=50).contains(&tx.client));
+            disputes += matches!(tx.payload, TxPayload::Dispute { .. }) as usize;
+            accepted += engine.process_transaction(tx).is_ok() as usize;
+        }
+
+        assert!(disputes > 1_000);
+        // Only withdrawals and disputes without enough available funds are rejected.
+        assert!(accepted > 9_000, "only {accepted} accepted");
+        assert!(engine.verify().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_transactions_are_rejected() {
+        let workload = Workload {
+            transactions: 10_000,
+            clients: 20,
+            mix: TransactionMix {
+                deposit: 1,
+                withdrawal: 0,
+                invalid: 1,
+                ..TransactionMix::DEPOSIT_HEAVY
+            },
+            ..Workload::new(11)
+        };
+
+        let mut engine = Engine::default();
+        let mut deposit_ids = std::collections::HashSet::new();
+        for tx in workload.generate() {
+            let valid = match tx.payload {
+                TxPayload::Deposit { amount } => amount.is_positive() && deposit_ids.insert(tx.id),
+                _ => false,
+            };
+            assert_eq!(engine.process_transaction(tx).is_ok(), valid, "{tx:?}");
+        }
+        assert!(deposit_ids.len() < 7_000);
+    }
+}
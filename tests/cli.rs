@@ -0,0 +1,410 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::{Child, Command, Output, Stdio},
+};
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_payment-engine"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The CLI may exit without reading stdin, e.g. on invalid arguments.
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_exit_codes() {
+    let accepted = "type,client,tx,amount\ndeposit,1,1,10\n";
+    let rejected = "type,client,tx,amount\ndeposit,1,1,10\nwithdrawal,1,2,20\n";
+    let malformed = "type,client,tx,amount\ndeposit,1,1,ten\n";
+
+    assert_eq!(run(&["-"], accepted).status.code(), Some(0));
+    assert_eq!(run(&["process", "-"], rejected).status.code(), Some(1));
+    assert_eq!(
+        run(&["--sort", "balance", "-"], accepted).status.code(),
+        Some(2)
+    );
+    for decimals in ["-1", "29", "two"] {
+        assert_eq!(
+            run(&["--decimals", decimals, "-"], accepted).status.code(),
+            Some(2)
+        );
+    }
+    let days = (u64::MAX / 60).to_string();
+    assert_eq!(
+        run(&["--dispute-window-days", &days, "-"], accepted)
+            .status
+            .code(),
+        Some(2)
+    );
+    assert_eq!(run(&["-"], malformed).status.code(), Some(3));
+    assert_eq!(run(&["missing.csv"], "").status.code(), Some(4));
+
+    let output = run(&["-"], rejected);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr,
+        "<stdin>:3: transaction 2 of client 1 rejected: insufficient available funds\n"
+    );
+}
+
+#[test]
+fn test_summarize_rejections() {
+    let input = "\
+type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,20
+withdrawal,1,3,30
+resolve,1,1,
+deposit,1,4,ten
+";
+    let output = run(&["validate", "--summarize-rejections", "-"], input);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "\
+2 transactions rejected: insufficient available funds
+1 transactions rejected: transaction is not disputed
+1 malformed rows
+error: 1 malformed rows
+"
+    );
+}
+
+#[test]
+fn test_replay_snapshot() {
+    let first = "\
+type,client,tx,amount,timestamp
+deposit,1,1,10,2024-01-01T00:00:00Z
+deposit,2,2,5,
+dispute,1,1,,
+";
+    let second = "type,client,tx,amount\nresolve,1,1,\nwithdrawal,2,3,1\n";
+
+    let snapshot = run(&["snapshot", "-"], first);
+    assert_eq!(snapshot.status.code(), Some(0));
+    let snapshot = String::from_utf8(snapshot.stdout).unwrap();
+    assert!(snapshot.starts_with("type,client,tx,amount,timestamp\n"));
+    assert_eq!(snapshot.lines().count(), 4);
+    assert!(snapshot.lines().all(|line| !line.ends_with(',')));
+
+    let dir = std::env::temp_dir().join(format!("payment-engine-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let snapshot_path = dir.join("snapshot.csv");
+    std::fs::write(&snapshot_path, snapshot).unwrap();
+
+    let replayed = run(&["replay", snapshot_path.to_str().unwrap(), "-"], second);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(replayed.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(replayed.stdout).unwrap(),
+        "client,available,held,total,locked\n1,10,0,10,false\n2,4,0,4,false\n"
+    );
+}
+
+#[test]
+fn test_validate_report() {
+    let input = "\
+type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,20
+withdrawal,1,3,2.5
+deposit,2,4,5
+dispute,2,4,
+chargeback,2,4,
+deposit,3,5,ten
+";
+
+    let output = run(&["validate", "-"], input);
+    assert_eq!(output.status.code(), Some(3));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["rows"], 7);
+    assert_eq!(report["malformed"], 1);
+    assert_eq!(report["accepted"], 5);
+    assert_eq!(report["rejected"], 1);
+    assert_eq!(
+        report["types"]["withdrawal"],
+        serde_json::json!({ "accepted": 1, "rejected": 1 })
+    );
+    assert_eq!(report["rejections"]["insufficient available funds"], 1);
+    assert_eq!(report["clients"], 2);
+    assert_eq!(report["locked_clients"], serde_json::json!([2]));
+    assert_eq!(
+        report["net_flows"][0],
+        serde_json::json!({
+            "client": 1,
+            "deposits": "10",
+            "withdrawals": "2.5",
+            "chargebacks": "0",
+            "net": "7.5"
+        })
+    );
+    assert_eq!(
+        report["net_flows"][1],
+        serde_json::json!({
+            "client": 2,
+            "deposits": "5",
+            "withdrawals": "0",
+            "chargebacks": "5",
+            "net": "0"
+        })
+    );
+    assert!(
+        !String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("available\"")
+    );
+
+    let valid = "type,client,tx,amount\ndeposit,1,1,10\n";
+    assert_eq!(run(&["validate", "-"], valid).status.code(), Some(0));
+}
+
+#[test]
+fn test_statement_dispute_summary() {
+    let input = "type,client,tx,amount,timestamp\ndeposit,1,1,10,1000\ndeposit,1,2,5,2000\n\
+                 dispute,1,1,,3000\nresolve,1,1,,4000\ndispute,1,2,,5000\n";
+
+    let csv = run(&["statement", "--client", "1", "-"], input);
+    let csv = String::from_utf8(csv.stdout).unwrap();
+    assert!(csv.ends_with(
+        "disputes,,,2,,,\ndisputed,,,15,,,\nresolved,,,10,,,\ncharged_back,,,0,,,\n\
+         reversed,,,0,,,\n"
+    ));
+
+    let json = run(
+        &["statement", "--client", "1", "--format", "json", "-"],
+        input,
+    );
+    let json: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    assert_eq!(json["disputes"]["count"], 2);
+}
+
+#[test]
+fn test_compressed_files() {
+    use std::io::Read;
+
+    let input = "type,client,tx,amount\ndeposit,1,1,10\nwithdrawal,1,2,20\n";
+    let dir = std::env::temp_dir().join(format!("payment-engine-gz-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(input.as_bytes()).unwrap();
+    std::fs::write(path("input.csv.gz"), encoder.finish().unwrap()).unwrap();
+
+    let output = run(
+        &[
+            &path("input.csv.gz"),
+            "--output",
+            &path("output.csv.zst"),
+            "--rejections",
+            &path("rejections.txt.gz"),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty() && output.stderr.is_empty());
+
+    let accounts = zstd::decode_all(std::fs::File::open(path("output.csv.zst")).unwrap()).unwrap();
+    let mut rejections = String::new();
+    flate2::read::GzDecoder::new(std::fs::File::open(path("rejections.txt.gz")).unwrap())
+        .read_to_string(&mut rejections)
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        String::from_utf8(accounts).unwrap(),
+        "client,available,held,total,locked\n1,10,0,10,false\n"
+    );
+    assert!(
+        rejections
+            .ends_with(":3: transaction 2 of client 1 rejected: insufficient available funds\n")
+    );
+}
+
+#[test]
+fn test_input_schema() {
+    let input = "DEPOSIT;1;1;10\nWithdrawal;1;2;4\n";
+    let output = run(&["process", "--no-headers", "--delimiter", ";", "-"], input);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,6,0,6,false\n"
+    );
+
+    let output = run(&["-"], "txn_type,client,tx,amount\ndeposit,1,1,10\n");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: <stdin>: missing column \"type\"\n"
+    );
+}
+
+#[test]
+fn test_idempotent_replays() {
+    let batch = "type,client,tx,amount\ndeposit,1,1,10\nwithdrawal,1,2,15\n";
+    let retried = "type,client,tx,amount\ndeposit,1,3,10\nwithdrawal,1,2,15\ndeposit,1,1,10\n";
+
+    let dir = std::env::temp_dir().join(format!("payment-engine-replays-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let batch_path = dir.join("batch.csv");
+    std::fs::write(&batch_path, batch).unwrap();
+    let args = ["--idempotent", batch_path.to_str().unwrap(), "-"];
+
+    // The retried rows are ignored, so only the first withdrawal counts as rejected.
+    let output = run(&args, retried);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,20,0,20,false\n"
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(
+        "<stdin>:3: transaction 2 of client 1 ignored: transaction was already processed\n"
+    ));
+
+    let output = run(&args, "type,client,tx,amount\ndeposit,1,1,12\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(String::from_utf8(output.stderr).unwrap().ends_with(
+        "<stdin>:2: transaction 1 of client 1 rejected as anomaly: transaction id was already used with different contents\n"
+    ));
+}
+
+#[test]
+fn test_conflicting_duplicates() {
+    let input = "type,client,tx,amount\ndeposit,1,1,10\ndeposit,1,1,10\ndeposit,1,1,12\n";
+
+    let output = run(&["stats", "-"], input);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("transactions: 1 accepted, 2 rejected, 0 ignored\n"));
+    assert!(stdout.contains("\nanomalies: 1\n"));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "<stdin>:3: transaction 1 of client 1 rejected: duplicate transaction id\n\
+         <stdin>:4: transaction 1 of client 1 rejected as anomaly: transaction id was already \
+         used with different contents\n"
+    );
+
+    let output = run(&["--fail-on-anomalies", "-"], input);
+    assert_eq!(output.status.code(), Some(6));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_engine_stats() {
+    let input = "type,client,tx,amount\ndeposit,1,1,10\ndeposit,2,2,5\nwithdrawal,2,3,8\n\
+                 dispute,1,1,\nchargeback,1,1,\n";
+
+    let output = run(&["--stats", "-"], input);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("\ntransactions: 4 accepted, 1 rejected, 0 ignored\n"));
+    assert!(stderr.contains("\n- insufficient available funds: 1\n"));
+    assert!(stderr.contains("\ndisputes: 1 opened, 0 resolved, 1 charged back\n"));
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .starts_with("client,")
+    );
+
+    let output = run(&["stats", "--stats=prometheus", "-"], input);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(
+        "\npayment_engine_transactions_total{type=\"withdrawal\",outcome=\"rejected\"} 1\n"
+    ));
+    assert!(stdout.contains("\npayment_engine_volume_total{kind=\"chargeback\"} 10\n"));
+    assert!(stdout.contains("\npayment_engine_clients 2\n"));
+    assert!(
+        !String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("payment_engine_")
+    );
+}
+
+/// Send an HTTP request to the server, returning the status line and the body.
+fn request(addr: &str, method: &str, path: &str, body: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\
+         Content-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+/// A server process, killed when dropped so that failed tests don't leave it running.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+fn test_serve_metrics() {
+    let mut server = Server(
+        Command::new(env!("CARGO_BIN_EXE_payment-engine"))
+            .args([
+                "serve",
+                "--listen",
+                "127.0.0.1:0",
+                "--max-body-bytes",
+                "256",
+            ])
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+    let mut stderr = BufReader::new(server.0.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    let addr = line.trim().strip_prefix("listening on http://").unwrap();
+
+    let batch = "type,client,tx,amount\ndeposit,1,1,10\ndeposit,2,2,5\ndispute,1,1,\n\
+                 withdrawal,2,3,8\n";
+    let (status, body) = request(addr, "POST", "/transactions", batch);
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(
+        body,
+        r#"{"anomalies":0,"batch":1,"ignored":0,"rejected":1,"rows":4}"#
+    );
+    let (status, _) = request(addr, "POST", "/transactions", "type,client,tx\nfoo,1,1\n");
+    assert_eq!(status, "HTTP/1.1 400 Bad Request");
+    let (status, _) = request(addr, "POST", "/transactions", &batch.repeat(4));
+    assert_eq!(status, "HTTP/1.1 413 Payload Too Large");
+
+    let (status, metrics) = request(addr, "GET", "/metrics", "");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    for line in [
+        "payment_engine_transactions_total{type=\"deposit\",outcome=\"accepted\"} 2",
+        "payment_engine_transactions_total{type=\"withdrawal\",outcome=\"rejected\"} 1",
+        "payment_engine_transaction_latency_seconds_count 4",
+        "payment_engine_batch_size_bucket{le=\"10\"} 1",
+        "payment_engine_batch_size_sum 4",
+        "payment_engine_clients 2",
+        "payment_engine_open_disputes 1",
+        "payment_engine_held_funds 10",
+    ] {
+        assert!(metrics.lines().any(|l| l == line), "{line}");
+    }
+
+    let (_, accounts) = request(addr, "GET", "/accounts", "");
+    assert_eq!(
+        accounts,
+        "client,available,held,total,locked\n1,0,10,10,false\n2,5,0,5,false\n"
+    );
+    assert_eq!(request(addr, "GET", "/", "").0, "HTTP/1.1 404 Not Found");
+}
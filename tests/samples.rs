@@ -0,0 +1,85 @@
+//! Runs the CLI against every `samples/*/input.csv` and compares its output with the
+//! sample's `output.csv`.
+//!
+//! Run `cargo test --test samples -- --bless` to overwrite the expected outputs with the
+//! current ones instead. Other arguments select the samples whose names contain them.
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, ExitCode},
+};
+
+fn main() -> ExitCode {
+    let mut bless = false;
+    let mut filters = vec![];
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            // Options passed by `cargo test` to every test target.
+            _ if arg.starts_with('-') => {}
+            _ => filters.push(arg),
+        }
+    }
+
+    let samples_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("samples");
+    let mut samples: Vec<_> = fs::read_dir(&samples_dir)
+        .expect("the samples directory should be readable")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.join("input.csv").is_file())
+        .collect();
+    samples.sort();
+
+    println!("\nrunning {} samples", samples.len());
+    let (mut passed, mut failed, mut filtered) = (0, 0, 0);
+
+    for sample in &samples {
+        let name = sample.file_name().unwrap().to_string_lossy();
+        if !filters.is_empty() && !filters.iter().any(|filter| name.contains(filter.as_str())) {
+            filtered += 1;
+            continue;
+        }
+
+        let output = Command::new(env!("CARGO_BIN_EXE_payment-engine"))
+            .arg(sample.join("input.csv"))
+            .output()
+            .expect("the CLI should run");
+        // Samples with rejected transactions exit with 1.
+        if !matches!(output.status.code(), Some(0 | 1)) {
+            println!("sample {name} ... FAILED");
+            println!("{}", String::from_utf8_lossy(&output.stderr));
+            failed += 1;
+            continue;
+        }
+
+        let expected_path = sample.join("output.csv");
+        if bless {
+            fs::write(&expected_path, &output.stdout).expect("the output should be writable");
+            println!("sample {name} ... blessed");
+            passed += 1;
+            continue;
+        }
+
+        let expected = fs::read(&expected_path).unwrap_or_default();
+        if output.stdout == expected {
+            println!("sample {name} ... ok");
+            passed += 1;
+        } else {
+            println!("sample {name} ... FAILED");
+            println!("expected:\n{}", String::from_utf8_lossy(&expected));
+            println!("actual:\n{}", String::from_utf8_lossy(&output.stdout));
+            failed += 1;
+        }
+    }
+
+    let result = if failed == 0 { "ok" } else { "FAILED" };
+    println!(
+        "\ntest result: {result}. {passed} passed; {failed} failed; {filtered} filtered out\n"
+    );
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        println!("run `cargo test --test samples -- --bless` if the new outputs are correct\n");
+        ExitCode::FAILURE
+    }
+}